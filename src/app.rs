@@ -1,48 +1,625 @@
-use std::sync::Arc;
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+    sync::Arc,
+    time::Instant,
+};
+
+use notify::RecommendedWatcher;
+use tokio::{
+    select,
+    sync::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender},
+};
 
-use tokio::sync::{mpsc::Receiver, Mutex};
+use tui::layout::Rect;
 
 use crate::{
     args::Args,
-    watchers::{self, file::FileWatcher},
+    command,
+    config::{Settings, Theme},
+    filter::{Condition, Filter, Matcher, Op},
+    format::{custom::Custom, Fields, FormatRule, Parser, Shown},
+    group::{self, GroupRule},
+    highlight::Rule,
+    keymap::{Keymap, Mode},
+    layout,
+    line::Line,
+    merge::MergedRev,
+    prompt::{Kind, Prompt},
+    search::Search,
+    stats::Rate,
+    view::View,
+    watchers::{
+        self,
+        command::CommandWatcher,
+        discovery::{Discovery, Spec},
+        file::{self, FileWatcher},
+        stdin::StdinWatcher,
+        SharedWatcher,
+    },
 };
 
+pub struct Pane {
+    pub watcher: SharedWatcher,
+    /// number of lines in the watcher's history the last time this pane was on screen
+    pub seen: usize,
+    pub view: View,
+    /// highlight rules that apply to this pane's source
+    pub highlights: Vec<Rule>,
+    pub rate: Rate,
+}
+
 pub struct App {
     pub tab: usize,
+    /// pane that receives pane-specific actions, and the main one in the main+stack layout
+    pub focus: usize,
+    pub layout: layout::Mode,
+    /// whether the "All" tab interleaves every source into a single pane
+    pub merged: bool,
+    pub merged_view: View,
+    /// where each pane was drawn on the last render. `None` stands for the merged view
+    pub regions: Vec<(Option<usize>, Rect)>,
+    /// search, filter or command being typed, if any
+    pub prompt: Option<Prompt>,
+    /// feedback shown in the status line until the next key press
+    pub message: Option<String>,
+    pub keymap: Keymap,
+    /// scroll offset of the help, while it's open
+    pub help: Option<u16>,
+    /// scroll offset of the details of the selected line, while they're shown
+    pub detail: Option<u16>,
+    /// whether long lines wrap rather than being cut at the edge of their pane
+    pub wrap: bool,
+    pub quit: bool,
+    pub theme: Theme,
+    /// names of the fields of structured lines
+    pub fields: Fields,
     pub receiver: Receiver<()>,
-    pub watchers: Vec<Arc<Mutex<FileWatcher>>>,
+    pub panes: Vec<Pane>,
+    tx: Sender<()>,
+    options: file::Options,
+    /// lines kept per source
+    history: usize,
+    filters: Vec<Filter>,
+    /// formats forced on sources, the last applying one winning
+    formats: Vec<FormatRule>,
+    /// formats defined in the config
+    customs: Vec<Arc<Custom>>,
+    /// how lines are grouped into records, the last applying rule winning
+    groupings: Vec<GroupRule>,
+    highlights: Vec<Rule>,
+    discoveries: UnboundedReceiver<Discovery>,
+    discovery_tx: UnboundedSender<Discovery>,
+    /// watches for files matching the patterns opened
+    discoverers: Vec<RecommendedWatcher>,
 }
 
 impl App {
-    pub async fn new(args: Args) -> anyhow::Result<Self> {
-        let mut watchers: Vec<_> = vec![];
+    pub async fn new(args: Args, settings: Settings) -> anyhow::Result<Self> {
+        let (tx, rx) = mpsc::channel::<()>(100);
+        let (discovery_tx, discovery_rx) = mpsc::unbounded_channel();
 
-        let (tx, rx) = tokio::sync::mpsc::channel::<()>(100);
+        let customs = settings.customs()?;
+        let formats = args
+            .format
+            .iter()
+            .map(|spec| FormatRule::parse(spec, &customs))
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-        for file in args.files {
-            let watcher = FileWatcher::new(&file)?;
-            watchers::listen(&watcher, tx.clone()).await?;
-            watchers.push(watcher);
-        }
-
-        Ok(Self {
+        let mut app = Self {
             tab: 0,
-            watchers,
+            focus: 0,
+            layout: settings.layout()?.unwrap_or(layout::Mode::Columns),
+            merged: false,
+            merged_view: View::default(),
+            regions: vec![],
+            prompt: None,
+            message: None,
+            keymap: settings.keymap()?,
+            help: None,
+            detail: None,
+            wrap: true,
+            quit: false,
+            theme: settings.theme()?,
+            fields: settings.fields(),
+            panes: vec![],
             receiver: rx,
-        })
+            tx,
+            options: file::Options {
+                backfill: args.lines,
+                drain_rotated: args.drain_rotated,
+            },
+            history: settings.history()?.unwrap_or(watchers::DEFAULT_HISTORY),
+            filters: settings.filters()?,
+            // rules given as arguments come last, so they win over the config file
+            highlights: settings
+                .highlights()?
+                .into_iter()
+                .chain(args.highlight)
+                .collect(),
+            // same for formats
+            formats: settings
+                .formats(&customs)?
+                .into_iter()
+                .chain(formats)
+                .collect(),
+            customs,
+            groupings: settings.groupings()?,
+            discoveries: discovery_rx,
+            discovery_tx,
+            discoverers: vec![],
+        };
+        app.merged_view = app.view();
+
+        let files: Vec<_> = settings.files.into_iter().chain(args.files).collect();
+        let commands = settings.commands.into_iter().chain(args.commands);
+
+        // data piped into us gets a pane of its own, or takes the place of a "-" file
+        let piped = !io::stdin().is_terminal();
+        if piped && !files.iter().any(|f| f == "-") {
+            let parser = app.parser(None);
+            app.add_pane(StdinWatcher::new(app.history, parser)?)
+                .await?;
+        }
+
+        for file in files {
+            app.open(&file).await?;
+        }
+        for command in commands {
+            app.run(&command).await?;
+        }
+
+        Ok(app)
+    }
+
+    /// Follows a file, every file matching a glob or in a directory, or stdin given "-"
+    pub async fn open(&mut self, file: &str) -> anyhow::Result<()> {
+        if file == "-" {
+            let parser = self.parser(None);
+            return self
+                .add_pane(StdinWatcher::new(self.history, parser)?)
+                .await;
+        }
+
+        match Spec::parse(file)? {
+            Some(spec) => {
+                for path in spec.matches()? {
+                    self.open_file(&path).await?;
+                }
+                self.discoverers
+                    .push(spec.watch(self.discovery_tx.clone())?);
+            }
+            None => self.open_file(file).await?,
+        }
+        Ok(())
+    }
+
+    /// Runs a shell command, following its output
+    pub async fn run(&mut self, command: &str) -> anyhow::Result<()> {
+        let parser = self.parser(None);
+        self.add_pane(CommandWatcher::new(command, self.history, parser)?)
+            .await
+    }
+
+    pub async fn close_focused(&mut self) {
+        if self.focus < self.panes.len() {
+            self.remove_pane(self.focus).await;
+        }
     }
 
     pub async fn wait(&mut self) {
-        self.receiver.recv().await;
+        select! {
+            _ = self.receiver.recv() => {}
+            Some(discovery) = self.discoveries.recv() => match discovery {
+                Discovery::Added(path) => {
+                    if let Err(e) = self.open_file(&path).await {
+                        self.message = Some(format!("cannot open {}: {:#}", path, e));
+                    }
+                }
+                Discovery::Removed(path) => self.close_file(&path).await,
+            },
+        }
     }
 
-    pub fn move_to_tab(&mut self, n: usize) {
-        if n == 0 {
+    async fn add_pane(&mut self, watcher: SharedWatcher) -> anyhow::Result<()> {
+        watchers::listen(&watcher, self.tx.clone()).await?;
+
+        let path = watcher.lock().await.path().map(String::from);
+        let highlights = self
+            .highlights
+            .iter()
+            .filter(|r| r.files.includes(path.as_deref()))
+            .cloned()
+            .collect();
+
+        self.panes.push(Pane {
+            watcher,
+            seen: 0,
+            view: self.view(),
+            highlights,
+            rate: Rate::default(),
+        });
+        Ok(())
+    }
+
+    /// A view of lines starting out with the filters of the config
+    fn view(&self) -> View {
+        let mut view = View::default();
+        for filter in &self.filters {
+            view.filters.push(filter.clone());
+        }
+        view
+    }
+
+    async fn open_file(&mut self, path: &str) -> anyhow::Result<()> {
+        if self.find_file(path).await.is_some() {
+            return Ok(());
+        }
+
+        let watcher = FileWatcher::new(
+            path,
+            self.history,
+            self.options.clone(),
+            self.parser(Some(path)),
+        )?;
+        self.add_pane(watcher).await
+    }
+
+    /// Parser for the lines of the source at the given path, in the format forced on it if any
+    fn parser(&self, path: Option<&str>) -> Parser {
+        let rule = self.formats.iter().rev().find(|r| r.files.includes(path));
+        let grouping = self.groupings.iter().rev().find(|r| r.files.includes(path));
+        Parser::new(
+            rule.map(|r| r.format.clone()),
+            &self.customs,
+            grouping.map(|r| r.grouping.clone()).unwrap_or_default(),
+            &self.fields,
+        )
+    }
+
+    async fn close_file(&mut self, path: &str) {
+        // the file may have been re-created in the meantime, which the watcher handles
+        if std::path::Path::new(path).exists() {
+            return;
+        }
+
+        if let Some(i) = self.find_file(path).await {
+            self.remove_pane(i).await;
+        }
+    }
+
+    async fn find_file(&self, path: &str) -> Option<usize> {
+        for (i, pane) in self.panes.iter().enumerate() {
+            if pane.watcher.lock().await.path() == Some(path) {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    async fn remove_pane(&mut self, i: usize) {
+        let pane = self.panes.remove(i);
+        pane.watcher.lock().await.stop();
+
+        if self.tab == i + 1 {
             self.tab = 0;
-        } else if n > self.watchers.len() + 1 {
+        } else if self.tab > i + 1 {
+            self.tab -= 1;
+        }
+        if self.focus > i || self.focus == self.panes.len() {
+            self.focus = self.focus.saturating_sub(1);
+        }
+    }
+
+    pub fn move_to_tab(&mut self, n: usize) {
+        if n == 0 || n > self.panes.len() + 1 {
             self.tab = 0;
         } else {
             self.tab = n - 1;
         }
+
+        if self.tab > 0 {
+            self.focus = self.tab - 1;
+        }
+    }
+
+    pub fn cycle_layout(&mut self) {
+        self.layout = self.layout.next();
+    }
+
+    pub fn focus_next(&mut self) {
+        if !self.panes.is_empty() {
+            self.focus = (self.focus + 1) % self.panes.len();
+            self.follow_focus();
+        }
+    }
+
+    pub fn focus_prev(&mut self) {
+        if !self.panes.is_empty() {
+            self.focus = (self.focus + self.panes.len() - 1) % self.panes.len();
+            self.follow_focus();
+        }
+    }
+
+    // when a single pane is shown, moving the focus switches tabs
+    fn follow_focus(&mut self) {
+        if self.tab > 0 {
+            self.tab = self.focus + 1;
+        }
+    }
+
+    /// the pane receiving pane-specific actions
+    pub fn focused(&self) -> Option<&Pane> {
+        self.panes.get(self.focus)
+    }
+
+    /// the view receiving scrolling and search actions
+    pub fn focused_view(&self) -> Option<&View> {
+        if self.tab == 0 && self.merged {
+            Some(&self.merged_view)
+        } else {
+            self.panes.get(self.focus).map(|p| &p.view)
+        }
+    }
+
+    pub fn focused_view_mut(&mut self) -> Option<&mut View> {
+        if self.tab == 0 && self.merged {
+            Some(&mut self.merged_view)
+        } else {
+            self.panes.get_mut(self.focus).map(|p| &mut p.view)
+        }
+    }
+
+    pub fn start_search(&mut self) {
+        let previous = self.focused_view_mut().and_then(|v| v.search.take());
+        let regex = previous.as_ref().is_some_and(|s| s.regex);
+        self.prompt = Some(Prompt::new(Kind::Search { previous }, regex));
+    }
+
+    pub fn start_filter(&mut self, exclude: bool) {
+        let kind = Kind::Filter {
+            exclude,
+            applied: false,
+        };
+        self.prompt = Some(Prompt::new(kind, true));
+    }
+
+    pub fn start_command(&mut self) {
+        self.prompt = Some(Prompt::new(Kind::Command, false));
+    }
+
+    /// What keys are for at the moment
+    pub fn mode(&self) -> Mode {
+        if self.help.is_some() {
+            return Mode::Help;
+        }
+
+        match self.prompt.as_ref().map(|p| &p.kind) {
+            None if self.focused_view().is_some_and(|v| v.cursor().is_some()) => Mode::Select,
+            None => Mode::Normal,
+            Some(Kind::Search { .. } | Kind::Filter { .. }) => Mode::Search,
+            Some(Kind::Command) => Mode::Command,
+        }
+    }
+
+    /// Changes what is being typed in the prompt, applying it right away
+    pub fn edit_prompt(&mut self, edit: impl FnOnce(&mut Prompt)) {
+        if let Some(mut prompt) = self.prompt.take() {
+            edit(&mut prompt);
+            prompt.candidates.clear();
+            if let Some(view) = self.focused_view_mut() {
+                apply_prompt(&mut prompt, view);
+            }
+            self.prompt = Some(prompt);
+        }
+    }
+
+    /// Starts selecting lines in the focused view, or stops and hides the details
+    pub fn toggle_select(&mut self) {
+        if let Some(view) = self.focused_view_mut() {
+            if view.cursor().is_some() {
+                view.unselect();
+                self.detail = None;
+            } else {
+                view.select();
+            }
+        }
+    }
+
+    /// Completes the command being typed as far as possible, keeping what it could be for display
+    pub fn complete_prompt(&mut self) {
+        if let Some(prompt) = &mut self.prompt {
+            if let Kind::Command = prompt.kind {
+                let completion = command::complete(&prompt.query);
+                prompt.query = completion.line;
+                prompt.candidates = completion.candidates;
+            }
+        }
+    }
+
+    /// Closes the prompt, returning the command typed if it was a command prompt
+    pub fn submit_prompt(&mut self) -> Option<String> {
+        let prompt = self.prompt.take()?;
+        match prompt.kind {
+            Kind::Command => Some(prompt.query),
+            _ => None,
+        }
+    }
+
+    /// Goes back to how the pane was before the prompt was opened
+    pub fn cancel_prompt(&mut self) {
+        if let Some(prompt) = self.prompt.take() {
+            if let Some(view) = self.focused_view_mut() {
+                match prompt.kind {
+                    Kind::Search { previous } => view.search = previous,
+                    Kind::Filter { applied, .. } => {
+                        if applied {
+                            view.filters.pop();
+                        }
+                    }
+                    Kind::Command => {}
+                }
+            }
+        }
+    }
+
+    /// Writes the lines the focused view shows to a file, oldest first and as they're shown,
+    /// returning how many there were. Records hidden by filters are left out, and folded ones are
+    /// written in full
+    pub async fn export(&self, path: &str) -> anyhow::Result<usize> {
+        let panes = self.focused_panes();
+        let view = match self.focused_view() {
+            Some(view) => view,
+            None => return Ok(0),
+        };
+
+        let tails =
+            futures::future::join_all(panes.iter().map(|&i| self.panes[i].watcher.lock())).await;
+        let lines = MergedRev::new(tails.iter().map(|t| t.history().rev_iter()));
+        let lines = lines
+            .map(|(source, line)| Shown::new(source, line, view.raw))
+            .collect();
+        let (mut lines, _) = group::keep(lines, &view.filters);
+        lines.reverse();
+
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        for line in &lines {
+            writeln!(file, "{}", line.text())?;
+        }
+        file.flush()?;
+        Ok(lines.len())
+    }
+
+    /// Filters the focused view on the value the selected record has for a field, like the app
+    /// of syslog lines
+    pub async fn filter_on_selected(&mut self, field: &str, exclude: bool) {
+        if self.focused_view().and_then(View::cursor).is_none() {
+            return;
+        }
+
+        let value = self
+            .with_selected(|line| {
+                let record = line.record.as_ref()?;
+                record.get(&[field]).map(String::from)
+            })
+            .await
+            .flatten();
+
+        match (value, self.focused_view_mut()) {
+            (Some(value), Some(view)) => view.filters.push(Filter {
+                matcher: Matcher::Field {
+                    condition: Condition {
+                        field: field.to_string(),
+                        op: Op::Equals(value),
+                    },
+                    text: None,
+                },
+                exclude,
+            }),
+            _ => self.message = Some(format!("the selected line has no {} field", field)),
+        }
+    }
+
+    /// Empties the history of the sources the focused view shows
+    pub async fn clear(&mut self) {
+        for i in self.focused_panes() {
+            self.panes[i].watcher.lock().await.clear();
+        }
+    }
+
+    /// Folds or unfolds the selected record
+    pub async fn toggle_fold(&mut self) {
+        if let Some(group) = self.with_selected(|line| line.group).await {
+            if let Some(view) = self.focused_view_mut() {
+                view.toggle_fold(group);
+            }
+        }
+    }
+
+    /// Runs `f` on the first line of the record the cursor is on, if it's still around
+    async fn with_selected<R>(&self, f: impl FnOnce(&Line) -> R) -> Option<R> {
+        let cursor = self.focused_view()?.cursor()?;
+        for i in self.focused_panes() {
+            let tail = self.panes[i].watcher.lock().await;
+            let history = tail.history();
+            if let Some(line) = history.rev_iter().find(|l| l.seq == cursor) {
+                let first = history.rev_iter().find(|l| l.seq == line.group);
+                return Some(f(first.unwrap_or(line)));
+            }
+        }
+        None
+    }
+
+    /// indexes of the panes whose lines the focused view shows
+    fn focused_panes(&self) -> Vec<usize> {
+        if self.tab == 0 && self.merged {
+            (0..self.panes.len()).collect()
+        } else if self.focus < self.panes.len() {
+            vec![self.focus]
+        } else {
+            vec![]
+        }
+    }
+
+    /// focuses whatever pane is drawn at the given position, and returns its view
+    pub fn view_at(&mut self, column: u16, row: u16) -> Option<&mut View> {
+        let (pane, _) = self.regions.iter().find(|(_, r)| {
+            (r.x..r.x + r.width).contains(&column) && (r.y..r.y + r.height).contains(&row)
+        })?;
+
+        match *pane {
+            Some(i) => {
+                self.focus = i;
+                Some(&mut self.panes[i].view)
+            }
+            None => Some(&mut self.merged_view),
+        }
+    }
+
+    /// indexes of the panes currently on screen
+    pub fn visible_panes(&self) -> Vec<usize> {
+        match self.tab {
+            0 => (0..self.panes.len()).collect(),
+            n => vec![n - 1],
+        }
+    }
+
+    pub fn mark_seen(&mut self, totals: &[usize]) {
+        for i in self.visible_panes() {
+            self.panes[i].seen = totals[i];
+        }
+    }
+
+    pub fn sample_rates(&mut self, totals: &[usize]) {
+        let now = Instant::now();
+        for (pane, total) in self.panes.iter_mut().zip(totals) {
+            pane.rate.sample(*total, now);
+        }
+    }
+}
+
+fn apply_prompt(prompt: &mut Prompt, view: &mut View) {
+    let typed = !prompt.query.is_empty();
+
+    match &mut prompt.kind {
+        Kind::Search { .. } => {
+            let search = Search::new(&prompt.query, prompt.regex);
+            prompt.invalid = search.is_err();
+            view.search = search.ok().filter(|_| typed);
+        }
+        Kind::Filter { exclude, applied } => {
+            let filter = Filter::parse(&prompt.query, prompt.regex, *exclude);
+            prompt.invalid = filter.is_err();
+
+            if *applied {
+                view.filters.pop();
+            }
+            *applied = false;
+            if let Some(filter) = filter.ok().filter(|_| typed) {
+                view.filters.push(filter);
+                *applied = true;
+            }
+        }
+        Kind::Command => {}
     }
 }
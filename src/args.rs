@@ -1,9 +1,41 @@
 use clap::Parser;
 
+use crate::highlight::Rule;
+
 #[derive(Parser, Debug)]
 pub struct Args {
+    /// File to follow. Use "-" to read from stdin, which is also followed whenever it's piped
     #[arg(short, long)]
     pub files: Vec<String>,
+
+    /// Shell command to run, following its output
+    #[arg(short, long = "cmd", value_name = "COMMAND")]
+    pub commands: Vec<String>,
+
+    /// Only load the last N lines of each file on startup, instead of the whole file
+    #[arg(short = 'n', long, value_name = "N")]
+    pub lines: Option<usize>,
+
+    /// When a file is rotated, finish reading the old one before following the new one
+    #[arg(long)]
+    pub drain_rotated: bool,
+
+    /// Style matches of REGEX, e.g. "req-[0-9a-f]+=underline". STYLE is a comma separated list of
+    /// colors ("yellow", "on blue", "#ff8800") and modifiers ("bold", "dim", ...). Add "line" to
+    /// style whole lines
+    #[arg(short = 'H', long, value_name = "REGEX=STYLE", value_parser = Rule::parse)]
+    pub highlight: Vec<Rule>,
+
+    /// Format of the lines of sources, instead of detecting it: plain, json, logfmt, syslog,
+    /// access or one defined in the config. Prefix it with a glob, as in "app*.log=logfmt", to
+    /// only apply it to matching files
+    #[arg(short = 'F', long, value_name = "[FILES=]FORMAT")]
+    pub format: Vec<String>,
+
+    /// Profile of the config file to use, adding its sources, filters and highlights to the
+    /// default ones
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
 }
 
 pub fn parse() -> Args {
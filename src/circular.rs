@@ -55,6 +55,8 @@ use std::iter;
 pub struct CircularBuffer<T> {
     buffer: Vec<T>,
     next_write_pos: usize,
+    /// elements pushed before the buffer was last cleared
+    cleared: usize,
 }
 #[allow(dead_code)]
 impl<T> CircularBuffer<T> {
@@ -63,6 +65,7 @@ impl<T> CircularBuffer<T> {
         CircularBuffer {
             buffer: Vec::with_capacity(max_depth),
             next_write_pos: 0,
+            cleared: 0,
         }
     }
     /// Return the number of elements present in the buffer
@@ -99,9 +102,15 @@ impl<T> CircularBuffer<T> {
         self.next_write_pos = 0;
         consumed
     }
+    /// Drop all elements, still counting them in the total
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.cleared += self.next_write_pos;
+        self.next_write_pos = 0;
+    }
     /// Total number of elements pushed into the buffer.
     pub fn total_elements(&self) -> usize {
-        self.next_write_pos
+        self.cleared + self.next_write_pos
     }
     /// If has_wrapped() is true, then elements have been overwritten
     pub fn has_wrapped(&self) -> bool {
@@ -109,7 +118,7 @@ impl<T> CircularBuffer<T> {
     }
     /// Return an iterator to step through all elements in the sequence,
     /// as these have been pushed (FIFO)
-    pub fn iter(&self) -> iter::Chain<std::slice::Iter<T>, std::slice::Iter<T>> {
+    pub fn iter(&self) -> iter::Chain<std::slice::Iter<'_, T>, std::slice::Iter<'_, T>> {
         let max_depth = self.buffer.capacity();
         if self.next_write_pos <= max_depth {
             // If buffer is not completely filled, then just iterate through it
@@ -125,7 +134,8 @@ impl<T> CircularBuffer<T> {
     /// as these have been pushed (LIFO)
     pub fn rev_iter(
         &self,
-    ) -> iter::Chain<std::iter::Rev<std::slice::Iter<T>>, std::iter::Rev<std::slice::Iter<T>>> {
+    ) -> iter::Chain<std::iter::Rev<std::slice::Iter<'_, T>>, std::iter::Rev<std::slice::Iter<'_, T>>>
+    {
         let max_depth = self.buffer.capacity();
         if self.next_write_pos <= max_depth {
             // If buffer is not completely filled, then just iterate through it
@@ -437,6 +447,24 @@ mod tests {
         }
     }
     #[test]
+    fn clear() {
+        use super::CircularBuffer;
+
+        let mut cb: CircularBuffer<u64> = CircularBuffer::new(5);
+        for i in 1..8 {
+            cb.push(i);
+        }
+        cb.clear();
+        assert!(cb.is_empty());
+        assert_eq!(cb.iter().next(), None);
+        assert_eq!(7, cb.total_elements());
+
+        cb.push(8);
+        assert_eq!(vec![&8], cb.rev_iter().collect::<Vec<_>>());
+        assert_eq!(8, cb.total_elements());
+        assert!(!cb.has_wrapped());
+    }
+    #[test]
     fn take() {
         use super::CircularBuffer;
 
@@ -0,0 +1,272 @@
+use std::{fs, path::Path};
+
+use crate::{keymap::UIAction, layout};
+
+/// A command typed after `:`
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// follow a file, glob or directory
+    Open(String),
+    /// follow the output of a shell command
+    Run(String),
+    /// stop following the focused source
+    Close,
+    /// add a filter to the focused pane, or remove them all when there is no query. Queries are
+    /// regexes, or conditions on fields like `level=error`
+    Filter {
+        query: Option<String>,
+        exclude: bool,
+    },
+    /// write the lines shown in the focused pane to a file
+    Export(String),
+    Layout(layout::Mode),
+    Set(Setting),
+    /// any action that can be bound to keys
+    Action(UIAction),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Setting {
+    Wrap(bool),
+}
+
+/// Commands other than actions, which take arguments
+const COMMANDS: &[&str] = &[
+    "open", "run", "close", "filter", "exclude", "export", "layout", "set",
+];
+const LAYOUTS: &[&str] = &["columns", "rows", "grid", "main+stack"];
+const SETTINGS: &[&str] = &["wrap on", "wrap off"];
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, arg) = match line.split_once(' ') {
+            Some((name, arg)) => (name, Some(arg.trim()).filter(|a| !a.is_empty())),
+            None => (line, None),
+        };
+        let required = |what: &str| {
+            arg.map(String::from)
+                .ok_or(format!(":{} needs {}", name, what))
+        };
+
+        Ok(match name {
+            "open" | "o" | "e" => Command::Open(required("a path")?),
+            "run" => Command::Run(required("a command")?),
+            "close" => Command::Close,
+            "filter" | "exclude" => Command::Filter {
+                query: arg.map(String::from),
+                exclude: name == "exclude",
+            },
+            "export" | "w" => Command::Export(required("a path")?),
+            "layout" => {
+                let name = required("a layout")?;
+                let mode = layout::Mode::from_name(&name).ok_or(format!(
+                    "unknown layout {}, expected {}",
+                    name,
+                    LAYOUTS.join(", ")
+                ))?;
+                Command::Layout(mode)
+            }
+            "set" => Command::Set(Setting::parse(&required("a setting")?)?),
+            "q" => Command::Action(UIAction::Quit),
+            name => Command::Action(
+                UIAction::from_name(name).ok_or(format!("unknown command: {}", name))?,
+            ),
+        })
+    }
+}
+
+impl Setting {
+    /// `wrap on`, `wrap off`, and the vim-like `wrap` and `nowrap`
+    fn parse(setting: &str) -> Result<Self, String> {
+        let words: Vec<_> = setting.split_whitespace().collect();
+        match words[..] {
+            ["wrap"] | ["wrap", "on"] => Ok(Setting::Wrap(true)),
+            ["nowrap"] | ["wrap", "off"] => Ok(Setting::Wrap(false)),
+            _ => Err(format!("unknown setting: {}", setting)),
+        }
+    }
+}
+
+/// Result of completing what was typed after `:`
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    /// the line with its last word completed as far as every candidate agrees
+    pub line: String,
+    pub candidates: Vec<String>,
+}
+
+/// Completes the command name, or its argument: paths for `:open` and `:export`, and the
+/// possible values of `:layout` and `:set`
+pub fn complete(line: &str) -> Completion {
+    let (candidates, start) = match line.split_once(' ') {
+        None => {
+            let mut names: Vec<_> = COMMANDS
+                .iter()
+                .map(|c| c.to_string())
+                .chain(UIAction::names())
+                .collect();
+            names.retain(|c| c.starts_with(line));
+            names.sort_unstable();
+            names.dedup();
+            (names, 0)
+        }
+        Some((name, arg)) => {
+            let start = name.len() + 1 + (arg.len() - arg.trim_start().len());
+            let arg = arg.trim_start();
+            let candidates = match name {
+                "open" | "o" | "e" | "export" | "w" => paths(arg),
+                "layout" => values(LAYOUTS, arg),
+                "set" => values(SETTINGS, arg),
+                _ => vec![],
+            };
+            (candidates, start)
+        }
+    };
+
+    let typed = &line[start..];
+    let common = common_prefix(&candidates);
+    let mut completed = format!("{}{}", &line[..start], common.unwrap_or(typed));
+    // a single candidate is done with, unless it's a directory to keep going into
+    if candidates.len() == 1 && !completed.ends_with('/') {
+        completed.push(' ');
+    }
+
+    Completion {
+        line: completed,
+        candidates,
+    }
+}
+
+fn values(values: &[&str], typed: &str) -> Vec<String> {
+    values
+        .iter()
+        .filter(|v| v.starts_with(typed))
+        .map(|v| v.to_string())
+        .collect()
+}
+
+/// Entries of the directory being typed starting with what was typed of the file name.
+/// Directories end with a slash
+fn paths(typed: &str) -> Vec<String> {
+    let (dir, prefix) = match typed.rfind('/') {
+        Some(i) => typed.split_at(i + 1),
+        None => ("", typed),
+    };
+    let entries = match fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn common_prefix(candidates: &[String]) -> Option<&str> {
+    let first = candidates.first()?;
+    let len = candidates.iter().skip(1).fold(first.len(), |len, c| {
+        first
+            .char_indices()
+            .zip(c.chars())
+            .take_while(|((i, a), b)| *i < len && a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+    });
+    Some(&first[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{complete, Command, Setting};
+    use crate::{keymap::UIAction, layout};
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            Command::parse("open logs/*.log"),
+            Ok(Command::Open("logs/*.log".into()))
+        );
+        assert_eq!(
+            Command::parse("filter  ERROR|WARN "),
+            Ok(Command::Filter {
+                query: Some("ERROR|WARN".into()),
+                exclude: false
+            })
+        );
+        assert_eq!(
+            Command::parse("exclude"),
+            Ok(Command::Filter {
+                query: None,
+                exclude: true
+            })
+        );
+        assert_eq!(
+            Command::parse("layout grid"),
+            Ok(Command::Layout(layout::Mode::Grid))
+        );
+        assert_eq!(
+            Command::parse("set wrap off"),
+            Ok(Command::Set(Setting::Wrap(false)))
+        );
+        assert_eq!(
+            Command::parse("toggle-pause"),
+            Ok(Command::Action(UIAction::TogglePause))
+        );
+
+        assert!(Command::parse("open").is_err());
+        assert!(Command::parse("layout diagonal").is_err());
+        assert!(Command::parse("set wrap maybe").is_err());
+        assert!(Command::parse("fly").is_err());
+    }
+
+    #[test]
+    fn completes_command_names_and_values() {
+        let completion = complete("ex");
+        assert_eq!(completion.line, "ex");
+        assert_eq!(completion.candidates, ["exclude", "exclude-app", "export"]);
+
+        assert_eq!(complete("exp").line, "export ");
+        assert_eq!(complete("toggle-p").line, "toggle-pause ");
+        assert_eq!(complete("layout m").line, "layout main+stack ");
+        assert_eq!(complete("set wrap o").line, "set wrap o");
+        assert!(complete("close x").candidates.is_empty());
+    }
+
+    #[test]
+    fn completes_paths() {
+        let root = std::env::temp_dir().join(format!("ninetails-complete-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("logs")).unwrap();
+        std::fs::write(root.join("api.log"), b"").unwrap();
+        std::fs::write(root.join("app.log"), b"").unwrap();
+        let dir = root.to_str().unwrap();
+
+        let completion = complete(&format!("open {}/a", dir));
+        assert_eq!(completion.line, format!("open {}/ap", dir));
+        assert_eq!(
+            completion.candidates,
+            [format!("{}/api.log", dir), format!("{}/app.log", dir)]
+        );
+
+        let completion = complete(&format!("export {}/l", dir));
+        assert_eq!(completion.line, format!("export {}/logs/", dir));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,640 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+use tui::style::{Color, Style};
+
+use crate::{
+    filter::Filter,
+    format::{custom::Custom, Fields, Format, FormatRule},
+    group::GroupRule,
+    highlight::{self, Rule},
+    keymap::{Keymap, Mode},
+    layout,
+    scope::FileScope,
+};
+
+/// name of the project-local config file, looked up from the current directory upwards
+const PROJECT_FILE: &str = ".ninetails.toml";
+
+/// Settings read from `~/.config/ninetails/config.toml` and the closest `.ninetails.toml`. The
+/// top-level settings always apply, and named profiles add to them
+#[derive(Debug, Default)]
+pub struct Config {
+    pub settings: Settings,
+    pub profiles: HashMap<String, Settings>,
+}
+
+/// Everything a config or profile can set, as in
+///
+/// ```toml
+/// files = ["logs/*.log"]
+/// commands = ["cargo run"]
+/// layout = "grid"
+/// history = 50000
+///
+/// [colors]
+/// focused = "yellow"
+///
+/// [[filter]]
+/// regex = "healthcheck"
+/// exclude = true
+///
+/// [[highlight]]
+/// regex = "req-[0-9a-f]+"
+/// style = "underline"
+/// files = "access*.log"
+///
+/// [[format]]
+/// name = "logfmt"
+/// files = "app*.log"
+///
+/// [[multiline]]
+/// start = '^\d{4}-\d{2}-\d{2}'
+/// files = "app*.log"
+///
+/// [custom-format.legacy]
+/// regex = '^(?P<ts>\S+ \S+) \[(?P<level>\w+)\] (?P<msg>.*)'
+/// time-format = "%d/%m/%Y %H:%M:%S"
+///
+/// [fields]
+/// message = "body"
+///
+/// [keys.normal]
+/// scroll-down = ["j", "<down>"]
+/// scroll-top = "gg"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub files: Vec<String>,
+    pub commands: Vec<String>,
+    pub layout: Option<String>,
+    /// lines kept per source
+    pub history: Option<usize>,
+    pub colors: Colors,
+    pub filter: Vec<FilterConfig>,
+    pub highlight: Vec<Highlight>,
+    pub format: Vec<FormatConfig>,
+    /// formats defined by a regex, by name
+    #[serde(rename = "custom-format")]
+    pub custom_format: BTreeMap<String, CustomFormat>,
+    pub multiline: Vec<Multiline>,
+    pub fields: FieldNames,
+    /// keys bound to each action, by mode
+    pub keys: BTreeMap<String, BTreeMap<String, Keys>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Styles of everything around the logs, in the format of highlight styles
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    pub text: Option<String>,
+    pub border: Option<String>,
+    pub focused: Option<String>,
+}
+
+/// Fields of structured lines holding their time, level and message, when they aren't named
+/// like usual
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldNames {
+    pub time: Option<String>,
+    pub level: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    pub regex: String,
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Highlight {
+    pub regex: String,
+    pub style: String,
+    /// style whole lines rather than just the matches
+    #[serde(default)]
+    pub line: bool,
+    #[serde(default)]
+    pub files: FileScope,
+}
+
+/// Format forced on sources rather than detected
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormatConfig {
+    pub name: String,
+    #[serde(default)]
+    pub files: FileScope,
+}
+
+/// A format whose fields are the named groups of a regex. The time is read from the group named
+/// like usual, as `time` or `ts`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFormat {
+    pub regex: String,
+    /// how the time is written, as in `%d/%m/%Y %H:%M:%S`
+    #[serde(rename = "time-format")]
+    pub time_format: Option<String>,
+}
+
+/// How lines are grouped into records, given a regex matching either the lines that start records
+/// or the ones that continue them
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Multiline {
+    pub start: Option<String>,
+    pub continuation: Option<String>,
+    #[serde(default)]
+    pub files: FileScope,
+}
+
+/// Resolved styles of everything around the logs
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub text: Style,
+    pub border: Style,
+    pub focused: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            text: Style::default().fg(Color::White).bg(Color::Black),
+            border: Style::default(),
+            focused: Style::default().fg(Color::Cyan),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the global config file and then the project one, if there are any
+    pub fn load() -> anyhow::Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(path) = global_path() {
+            config = config.merge(Self::from_file(&path)?.unwrap_or_default());
+        }
+        if let Some(path) = project_path() {
+            let project = Self::from_file(&path)?.unwrap_or_default();
+            config = config.merge(project.relative_to(path.parent().unwrap()));
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> anyhow::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("could not read {}", path.display())),
+        };
+
+        Self::parse(&text)
+            .map(Some)
+            .with_context(|| format!("invalid config in {}", path.display()))
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into()?,
+            None => HashMap::new(),
+        };
+
+        Ok(Self {
+            settings: toml::Value::Table(table).try_into()?,
+            profiles,
+        })
+    }
+
+    /// Settings to run with, given the name of the profile picked
+    pub fn settings(mut self, profile: Option<&str>) -> anyhow::Result<Settings> {
+        let name = match profile {
+            Some(name) => name,
+            None => return Ok(self.settings),
+        };
+
+        match self.profiles.remove(name) {
+            Some(profile) => Ok(self.settings.merge(profile)),
+            None => {
+                let mut names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                match names.len() {
+                    0 => bail!("unknown profile {:?}, no profiles are configured", name),
+                    _ => bail!(
+                        "unknown profile {:?}, expected one of {}",
+                        name,
+                        names.join(", ")
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Layers `other` on top of this config. Profiles with the same name are merged too
+    fn merge(mut self, other: Self) -> Self {
+        self.settings = self.settings.merge(other.settings);
+        for (name, profile) in other.profiles {
+            let merged = match self.profiles.remove(&name) {
+                Some(existing) => existing.merge(profile),
+                None => profile,
+            };
+            self.profiles.insert(name, merged);
+        }
+        self
+    }
+
+    /// Makes the relative paths of files relative to `dir` rather than to wherever we run from
+    fn relative_to(mut self, dir: &Path) -> Self {
+        if env::current_dir().is_ok_and(|cwd| cwd == dir) {
+            return self;
+        }
+
+        for settings in std::iter::once(&mut self.settings).chain(self.profiles.values_mut()) {
+            for file in &mut settings.files {
+                if Path::new(file).is_relative() && file != "-" {
+                    *file = dir.join(&file).to_string_lossy().into_owned();
+                }
+            }
+        }
+        self
+    }
+}
+
+impl Settings {
+    /// Layers `other` on top of these settings. Lists are added to, everything else replaced
+    pub fn merge(mut self, other: Self) -> Self {
+        self.files.extend(other.files);
+        self.commands.extend(other.commands);
+        self.layout = other.layout.or(self.layout);
+        self.history = other.history.or(self.history);
+        self.colors = Colors {
+            text: other.colors.text.or(self.colors.text),
+            border: other.colors.border.or(self.colors.border),
+            focused: other.colors.focused.or(self.colors.focused),
+        };
+        self.filter.extend(other.filter);
+        self.highlight.extend(other.highlight);
+        self.format.extend(other.format);
+        self.custom_format.extend(other.custom_format);
+        self.multiline.extend(other.multiline);
+        self.fields = FieldNames {
+            time: other.fields.time.or(self.fields.time),
+            level: other.fields.level.or(self.fields.level),
+            message: other.fields.message.or(self.fields.message),
+        };
+        for (mode, keys) in other.keys {
+            self.keys.entry(mode).or_default().extend(keys);
+        }
+        self
+    }
+
+    pub fn layout(&self) -> anyhow::Result<Option<layout::Mode>> {
+        self.layout
+            .as_deref()
+            .map(|name| {
+                layout::Mode::from_name(name).ok_or_else(|| {
+                    anyhow!(
+                        "unknown layout {:?}, expected columns, rows, grid or main+stack",
+                        name
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Lines kept per source, which can't be none
+    pub fn history(&self) -> anyhow::Result<Option<usize>> {
+        match self.history {
+            Some(0) => bail!("invalid history 0, sources need to keep at least 1 line"),
+            history => Ok(history),
+        }
+    }
+
+    pub fn theme(&self) -> anyhow::Result<Theme> {
+        let mut theme = Theme::default();
+        let colors = [
+            (&self.colors.text, &mut theme.text),
+            (&self.colors.border, &mut theme.border),
+            (&self.colors.focused, &mut theme.focused),
+        ];
+        for (spec, style) in colors {
+            if let Some(spec) = spec {
+                *style = style.patch(highlight::parse_style(spec)?);
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn filters(&self) -> anyhow::Result<Vec<Filter>> {
+        self.filter
+            .iter()
+            .map(|f| {
+                Filter::parse(&f.regex, true, f.exclude)
+                    .with_context(|| format!("invalid filter {:?}", f.regex))
+            })
+            .collect()
+    }
+
+    pub fn highlights(&self) -> anyhow::Result<Vec<Rule>> {
+        self.highlight
+            .iter()
+            .map(|h| Rule::new(&h.regex, &h.style, h.line, h.files.clone()))
+            .collect()
+    }
+
+    /// Formats forced on sources, given the custom ones
+    pub fn formats(&self, customs: &[Arc<Custom>]) -> anyhow::Result<Vec<FormatRule>> {
+        self.format
+            .iter()
+            .map(|f| FormatRule::new(&f.name, f.files.clone(), customs))
+            .collect()
+    }
+
+    pub fn customs(&self) -> anyhow::Result<Vec<Arc<Custom>>> {
+        self.custom_format
+            .iter()
+            .map(|(name, f)| {
+                if Format::from_name(name, &[]).is_some() {
+                    bail!("format {:?} is built in", name);
+                }
+                let custom = Custom::new(name, &f.regex, f.time_format.as_deref())
+                    .with_context(|| format!("invalid format {:?}", name))?;
+                Ok(Arc::new(custom))
+            })
+            .collect()
+    }
+
+    pub fn groupings(&self) -> anyhow::Result<Vec<GroupRule>> {
+        self.multiline
+            .iter()
+            .map(|m| {
+                GroupRule::new(
+                    m.start.as_deref(),
+                    m.continuation.as_deref(),
+                    m.files.clone(),
+                )
+                .context("invalid multiline rule")
+            })
+            .collect()
+    }
+
+    /// Names of the fields of structured lines, the configured ones replacing the usual ones
+    pub fn fields(&self) -> Fields {
+        let mut fields = Fields::default();
+        let names = [
+            (&self.fields.time, &mut fields.time),
+            (&self.fields.level, &mut fields.level),
+            (&self.fields.message, &mut fields.message),
+        ];
+        for (name, names) in names {
+            if let Some(name) = name {
+                *names = vec![name.clone()];
+            }
+        }
+        fields
+    }
+
+    /// Default bindings, with the ones configured for each action replacing them
+    pub fn keymap(&self) -> anyhow::Result<Keymap> {
+        let mut keymap = Keymap::default();
+
+        for (mode, bindings) in &self.keys {
+            let mode = Mode::from_name(mode).ok_or_else(|| {
+                anyhow!(
+                    "unknown mode {:?}, expected normal, search, command, select or help",
+                    mode
+                )
+            })?;
+            for (action, keys) in bindings {
+                let keys = match keys {
+                    Keys::One(key) => std::slice::from_ref(key),
+                    Keys::Many(keys) => keys,
+                };
+                keymap
+                    .bind(mode, action, keys)
+                    .with_context(|| format!("invalid keys for {}", action))?;
+            }
+        }
+
+        Ok(keymap)
+    }
+}
+
+fn global_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ninetails").join("config.toml"))
+}
+
+fn project_path() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::filter::Matcher;
+    use std::path::Path;
+
+    const CONFIG: &str = r#"
+        history = 1000
+        layout = "rows"
+
+        [[highlight]]
+        regex = "ERROR"
+        style = "red"
+        line = true
+
+        [profiles.api-dev]
+        files = ["logs/api.log"]
+        commands = ["cargo run"]
+        layout = "grid"
+
+        [[profiles.api-dev.filter]]
+        regex = "healthcheck"
+        exclude = true
+
+        [[profiles.api-dev.highlight]]
+        regex = "GET"
+        style = "green"
+        files = "access.log"
+    "#;
+
+    #[test]
+    fn filters_can_match_fields() {
+        let config = Config::parse("[[filter]]\nregex = \"status >= 500\"\n").unwrap();
+
+        let filters = config.settings(None).unwrap().filters().unwrap();
+        assert!(matches!(filters[0].matcher, Matcher::Field { .. }));
+    }
+
+    #[test]
+    fn profiles_add_to_the_top_level_settings() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        let settings = config.settings(Some("api-dev")).unwrap();
+        assert_eq!(settings.files, ["logs/api.log"]);
+        assert_eq!(settings.commands, ["cargo run"]);
+        assert_eq!(settings.layout.as_deref(), Some("grid"));
+        assert_eq!(settings.history, Some(1000));
+        assert_eq!(settings.filters().unwrap().len(), 1);
+
+        let rules = settings.highlights().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].line);
+        assert!(!rules[1].files.includes(Some("error.log")));
+    }
+
+    #[test]
+    fn rejects_unknown_profiles_and_settings() {
+        let config = Config::parse(CONFIG).unwrap();
+        let error = config.settings(Some("prod")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown profile \"prod\", expected one of api-dev"
+        );
+
+        assert!(Config::parse("[profiles.x]\nfile = []").is_err());
+        assert!(Config::parse("histroy = 1").is_err());
+    }
+
+    #[test]
+    fn keeps_at_least_one_line() {
+        let config = Config::parse("history = 0").unwrap();
+        assert!(config.settings.history().is_err());
+
+        let config = Config::parse("[profiles.x]\nhistory = 0").unwrap();
+        let settings = config.settings(Some("x")).unwrap();
+        assert!(settings.history().is_err());
+    }
+
+    #[test]
+    fn project_configs_layer_over_global_ones() {
+        let global = Config::parse(CONFIG).unwrap();
+        let project = Config::parse(
+            r#"
+            layout = "columns"
+            [profiles.api-dev]
+            files = ["logs/worker.log"]
+            "#,
+        )
+        .unwrap();
+
+        let config = global.merge(project.relative_to(Path::new("/srv/api")));
+        assert_eq!(config.settings.layout.as_deref(), Some("columns"));
+
+        let settings = config.settings(Some("api-dev")).unwrap();
+        assert_eq!(settings.files, ["logs/api.log", "/srv/api/logs/worker.log"]);
+    }
+
+    #[test]
+    fn renames_fields() {
+        let config = Config::parse("[fields]\nmessage = \"body\"").unwrap();
+
+        let fields = config.settings.fields();
+        assert_eq!(fields.message, ["body"]);
+        assert!(fields.level.contains(&"level".to_string()));
+    }
+
+    #[test]
+    fn forces_formats() {
+        let config = Config::parse(
+            r#"
+            [[format]]
+            name = "logfmt"
+            files = "app*.log"
+            "#,
+        )
+        .unwrap();
+
+        let formats = config.settings.formats(&[]).unwrap();
+        assert!(formats[0].files.includes(Some("/var/log/app.log")));
+        assert!(!formats[0].files.includes(Some("/var/log/db.log")));
+
+        let config = Config::parse("[[format]]\nname = \"xml\"").unwrap();
+        assert!(config.settings.formats(&[]).is_err());
+        assert!(Config::parse("[[format]]\nname = \"json\"\nfiles = \"[a\"").is_err());
+    }
+
+    #[test]
+    fn defines_formats() {
+        let config = Config::parse(
+            r#"
+            [custom-format.legacy]
+            regex = '^(?P<ts>\S+) (?P<msg>.*)'
+            time-format = "%H:%M:%S"
+
+            [[format]]
+            name = "legacy"
+            "#,
+        )
+        .unwrap();
+
+        let customs = config.settings.customs().unwrap();
+        assert_eq!(customs[0].name, "legacy");
+        assert!(config.settings.formats(&customs).is_ok());
+
+        let config = Config::parse("[custom-format.json]\nregex = '(?P<msg>.*)'").unwrap();
+        assert!(config.settings.customs().is_err());
+        // not to be mistaken for the rules forcing formats
+        assert!(Config::parse("[formats.legacy]\nregex = '(?P<msg>.*)'").is_err());
+    }
+
+    #[test]
+    fn groups_lines() {
+        let config = Config::parse(
+            r#"
+            [[multiline]]
+            start = '^\d{4}-'
+            files = "app.log"
+            "#,
+        )
+        .unwrap();
+        let groupings = config.settings.groupings().unwrap();
+        assert!(groupings[0]
+            .grouping
+            .continues("Traceback (most recent call last):"));
+
+        let config = Config::parse("[[multiline]]\nfiles = \"app.log\"").unwrap();
+        assert!(config.settings.groupings().is_err());
+    }
+
+    #[test]
+    fn binds_keys() {
+        let config = Config::parse(
+            r#"
+            [keys.normal]
+            scroll-down = ["j", "<down>"]
+            quit = "Q"
+            "#,
+        )
+        .unwrap();
+        assert!(config.settings.keymap().is_ok());
+
+        let config = Config::parse("[keys.insert]\nquit = \"q\"").unwrap();
+        assert!(config.settings.keymap().is_err());
+        let config = Config::parse("[keys.normal]\nquit = \"<hyper-q>\"").unwrap();
+        assert!(config.settings.keymap().is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{format::Record, search};
+
+/// Regexes and conditions on fields deciding which lines a pane shows. A line is shown when it
+/// matches every include filter and none of the exclude ones
+#[derive(Default)]
+pub struct Filters {
+    filters: Vec<Filter>,
+    /// whether the filters are temporarily switched off
+    pub disabled: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Filter {
+    pub matcher: Matcher,
+    pub exclude: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// matches the text of lines
+    Text(Regex),
+    /// matches a field of structured lines, which don't match if they don't have it. Lines in no
+    /// format we know are matched on their text instead, as the filter was typed if it was
+    Field {
+        condition: Condition,
+        text: Option<Regex>,
+    },
+}
+
+/// A condition on a field, as in `level=error` or `status>=500`
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub field: String,
+    pub op: Op,
+}
+
+#[derive(Clone, Debug)]
+pub enum Op {
+    /// `=`, ignoring case
+    Equals(String),
+    /// `!=`, ignoring case
+    Differs(String),
+    /// `~`
+    Matches(Regex),
+    /// `<`, `<=`, `>` and `>=`, on fields holding numbers
+    Below(f64),
+    AtMost(f64),
+    Above(f64),
+    AtLeast(f64),
+}
+
+impl Filter {
+    /// Parses a filter typed by the user: a condition on a field like `level=error`, or else text
+    /// to look for, which is a regex if `regex` is set
+    pub fn parse(query: &str, regex: bool, exclude: bool) -> Result<Self, regex::Error> {
+        let text = search::pattern(query, regex);
+        let matcher = match Condition::parse(query) {
+            Some(condition) => Matcher::Field {
+                condition: condition?,
+                text: text.ok(),
+            },
+            None => Matcher::Text(text?),
+        };
+        Ok(Self { matcher, exclude })
+    }
+
+    /// Whether a line matches, regardless of whether the filter keeps or hides what matches
+    pub fn matches(&self, text: &str, record: Option<&Record>) -> bool {
+        match &self.matcher {
+            Matcher::Text(pattern) => pattern.is_match(text),
+            Matcher::Field {
+                condition,
+                text: pattern,
+            } => match (record, pattern) {
+                (Some(record), _) => condition.matches(record),
+                (None, Some(pattern)) => pattern.is_match(text),
+                (None, None) => false,
+            },
+        }
+    }
+}
+
+impl Condition {
+    /// Parses `FIELD OP VALUE`, returning `None` for anything that isn't one
+    pub fn parse(query: &str) -> Option<Result<Self, regex::Error>> {
+        let name_end = query
+            .find(|c: char| !(c.is_alphanumeric() || "_.@-".contains(c)))
+            .filter(|&end| end > 0)?;
+        if !query.starts_with(|c: char| c.is_alphabetic() || "_@".contains(c)) {
+            return None;
+        }
+        let (field, rest) = query.split_at(name_end);
+        let rest = rest.trim_start();
+
+        // longest operators first
+        let (op, value) = ["!=", "<=", ">=", "=", "~", "<", ">"]
+            .into_iter()
+            .find_map(|op| Some((op, rest.strip_prefix(op)?.trim())))?;
+        let number = || value.parse::<f64>().ok();
+        let op = match op {
+            "=" => Op::Equals(value.to_string()),
+            "!=" => Op::Differs(value.to_string()),
+            "~" => match RegexBuilder::new(value)
+                .case_insensitive(!value.chars().any(char::is_uppercase))
+                .build()
+            {
+                Ok(pattern) => Op::Matches(pattern),
+                Err(e) => return Some(Err(e)),
+            },
+            "<" => Op::Below(number()?),
+            "<=" => Op::AtMost(number()?),
+            ">" => Op::Above(number()?),
+            _ => Op::AtLeast(number()?),
+        };
+
+        Some(Ok(Self {
+            field: field.to_string(),
+            op,
+        }))
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        let Some(value) = record.get(&[&self.field]) else {
+            return false;
+        };
+        let number = || value.trim().parse::<f64>().ok();
+
+        match &self.op {
+            Op::Equals(expected) => value.eq_ignore_ascii_case(expected),
+            Op::Differs(expected) => !value.eq_ignore_ascii_case(expected),
+            Op::Matches(pattern) => pattern.is_match(value),
+            Op::Below(n) => number().is_some_and(|v| v < *n),
+            Op::AtMost(n) => number().is_some_and(|v| v <= *n),
+            Op::Above(n) => number().is_some_and(|v| v > *n),
+            Op::AtLeast(n) => number().is_some_and(|v| v >= *n),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Matcher::Text(pattern) => write!(f, "/{}/", pattern),
+            Matcher::Field {
+                condition: Condition { field, op },
+                ..
+            } => match op {
+                Op::Equals(value) => write!(f, "{}={}", field, value),
+                Op::Differs(value) => write!(f, "{}!={}", field, value),
+                Op::Matches(pattern) => write!(f, "{}~{}", field, pattern),
+                Op::Below(n) => write!(f, "{}<{}", field, n),
+                Op::AtMost(n) => write!(f, "{}<={}", field, n),
+                Op::Above(n) => write!(f, "{}>{}", field, n),
+                Op::AtLeast(n) => write!(f, "{}>={}", field, n),
+            },
+        }
+    }
+}
+
+impl Filters {
+    /// Whether something is shown, given whether it matches the filter at each index
+    pub fn keeps_matching(&self, mut matches: impl FnMut(usize) -> bool) -> bool {
+        self.disabled
+            || self
+                .filters
+                .iter()
+                .enumerate()
+                .all(|(i, f)| matches(i) != f.exclude)
+    }
+
+    pub fn push(&mut self, filter: Filter) {
+        self.filters.push(filter);
+    }
+
+    /// Removes the filter added last
+    pub fn pop(&mut self) -> Option<Filter> {
+        self.filters.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.filters.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Filter> {
+        self.filters.iter()
+    }
+}
+
+#[cfg(test)]
+impl Filters {
+    /// Whether a line is shown, given its text and fields if it has any
+    pub fn keeps(&self, text: &str, record: Option<&Record>) -> bool {
+        self.keeps_matching(|i| self.filters[i].matches(text, record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Filters, Matcher};
+    use crate::format::Record;
+    use regex::Regex;
+
+    fn filter(pattern: &str, exclude: bool) -> Filter {
+        Filter {
+            matcher: Matcher::Text(Regex::new(pattern).unwrap()),
+            exclude,
+        }
+    }
+
+    #[test]
+    fn stacks_filters() {
+        let mut filters = Filters::default();
+        assert!(filters.keeps("INFO started", None));
+
+        filters.push(filter("ERROR|WARN", false));
+        assert!(!filters.keeps("INFO started", None));
+        assert!(filters.keeps("WARN disk almost full", None));
+        assert!(filters.keeps("ERROR healthcheck failed", None));
+
+        filters.push(filter("healthcheck", true));
+        assert!(filters.keeps("WARN disk almost full", None));
+        assert!(!filters.keeps("ERROR healthcheck failed", None));
+
+        filters.pop();
+        assert!(filters.keeps("ERROR healthcheck failed", None));
+    }
+
+    #[test]
+    fn can_be_switched_off() {
+        let mut filters = Filters::default();
+        filters.push(filter("ERROR", false));
+
+        filters.disabled = true;
+        assert!(filters.keeps("INFO started", None));
+    }
+
+    #[test]
+    fn filters_on_fields() {
+        let error = Record::from_pairs(&[
+            ("level", "ERROR"),
+            ("status", "503"),
+            ("path", "/api/users"),
+        ]);
+        let info = Record::from_pairs(&[("level", "info"), ("status", "200"), ("path", "/health")]);
+        let keeps = |query: &str, exclude: bool, record: Option<&Record>| {
+            let mut filters = Filters::default();
+            filters.push(Filter::parse(query, false, exclude).unwrap());
+            filters.keeps("user=ada level=error", record)
+        };
+
+        assert!(keeps("level=error", false, Some(&error)));
+        assert!(!keeps("level=error", false, Some(&info)));
+        assert!(keeps("level!=error", false, Some(&info)));
+        assert!(keeps("status>=500", false, Some(&error)));
+        assert!(!keeps("status >= 500", false, Some(&info)));
+        assert!(keeps("status<300", false, Some(&info)));
+        assert!(keeps("path~^/api", false, Some(&error)));
+        assert!(!keeps("path~^/api", true, Some(&error)));
+
+        // structured lines without the field don't match, even if their text does
+        assert!(!keeps("user=ada", false, Some(&error)));
+        // other lines are matched on their text
+        assert!(keeps("user=ada", false, None));
+        assert!(!keeps("user=bob", false, None));
+        assert!(!keeps("user=ada", true, None));
+    }
+
+    #[test]
+    fn parses_text_filters_and_conditions() {
+        let shown = |query: &str| {
+            Filter::parse(query, false, false)
+                .unwrap()
+                .matcher
+                .to_string()
+        };
+
+        assert_eq!(shown("level = error"), "level=error");
+        assert_eq!(shown("http.status>=500"), "http.status>=500");
+        // not a number, so not a comparison
+        assert_eq!(shown("a > b"), "/a > b/");
+        assert_eq!(shown("ERROR"), "/ERROR/");
+        assert_eq!(shown("=x"), "/=x/");
+        assert!(Filter::parse("path~(", false, false).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use super::Record;
+
+/// Parses web server access logs in the common or combined format, as in
+/// `1.2.3.4 - ada [10/Feb/2023:13:45:12 +0000] "GET / HTTP/1.1" 200 512 "-" "curl/7.0"`.
+/// Whatever is logged after that is kept too: a number as the latency, as logged by
+/// `$request_time` or `%D`, and `key=value` pairs under their own names
+pub fn parse(text: &str) -> Option<Record> {
+    let (client, rest) = text.split_once(' ')?;
+    let (ident, rest) = rest.split_once(' ')?;
+    let (user, rest) = rest.split_once(' ')?;
+    let (time, rest) = rest.strip_prefix('[')?.split_once("] ")?;
+    let (request, rest) = quoted(rest)?;
+    let (status, rest) = token(rest);
+    let (bytes, mut rest) = token(rest);
+
+    if client.is_empty() || time.len() < 20 {
+        return None;
+    }
+    if status.len() != 3 || !status.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if bytes != "-" && !bytes.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut fields = vec![("time".to_string(), time.to_string())];
+    let mut push = |name: &str, value: &str| {
+        if value != "-" && !value.is_empty() {
+            fields.push((name.to_string(), value.to_string()));
+        }
+    };
+    push("client", client);
+    push("ident", ident);
+    push("user", user);
+    match request.splitn(3, ' ').collect::<Vec<_>>()[..] {
+        [method, path, protocol] => {
+            push("method", method);
+            push("path", path);
+            push("protocol", protocol);
+        }
+        _ => push("request", &request),
+    }
+    push("status", status);
+    push("bytes", bytes);
+
+    // combined adds the referer and user agent
+    if let Some((referer, after)) = quoted(rest) {
+        let (agent, after) = quoted(after)?;
+        push("referer", &referer);
+        push("agent", &agent);
+        rest = after;
+    }
+
+    let mut rest = rest.trim_start();
+    while !rest.is_empty() {
+        let (value, after) = token(rest);
+        match value.split_once('=') {
+            Some((name, value)) => push(name, value.trim_matches('"')),
+            None if value.parse::<f64>().is_ok() => push("latency", value),
+            None => return None,
+        }
+        rest = after;
+    }
+
+    Some(Record { fields, time: None })
+}
+
+/// Reads a double-quoted string, returning it and what comes after the space following it
+fn quoted(text: &str) -> Option<(String, &str)> {
+    let text = text.strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let rest = &text[i + 1..];
+                return Some((value, rest.strip_prefix(' ').unwrap_or(rest)));
+            }
+            '\\' => match chars.next()? {
+                (_, '"') => value.push('"'),
+                (_, c) => {
+                    value.push('\\');
+                    value.push(c);
+                }
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn token(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    text.split_once(' ').unwrap_or((text, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::format::Record;
+
+    #[test]
+    fn parses_combined_lines() {
+        assert_eq!(
+            parse(
+                r#"10.0.0.7 - ada [10/Feb/2023:13:45:12 +0000] "GET /api/users?page=2 HTTP/1.1" 503 1024 "https://example.com/" "Mozilla/5.0 (X11; \"Linux\")" 0.215 upstream=10.0.1.2:8080"#
+            ),
+            Some(Record::from_pairs(&[
+                ("time", "10/Feb/2023:13:45:12 +0000"),
+                ("client", "10.0.0.7"),
+                ("user", "ada"),
+                ("method", "GET"),
+                ("path", "/api/users?page=2"),
+                ("protocol", "HTTP/1.1"),
+                ("status", "503"),
+                ("bytes", "1024"),
+                ("referer", "https://example.com/"),
+                ("agent", "Mozilla/5.0 (X11; \"Linux\")"),
+                ("latency", "0.215"),
+                ("upstream", "10.0.1.2:8080"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_common_lines() {
+        assert_eq!(
+            parse(r#"::1 - - [10/Feb/2023:13:45:12 +0000] "\x16\x03\x01" 400 -"#),
+            Some(Record::from_pairs(&[
+                ("time", "10/Feb/2023:13:45:12 +0000"),
+                ("client", "::1"),
+                ("request", "\\x16\\x03\\x01"),
+                ("status", "400"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(
+            parse("10.0.0.7 - - [yesterday] \"GET / HTTP/1.1\" 200 5"),
+            None
+        );
+        assert_eq!(
+            parse(r#"10.0.0.7 - - [10/Feb/2023:13:45:12 +0000] "GET / HTTP/1.1" OK 5"#),
+            None
+        );
+        assert_eq!(parse("INFO started in 5 ms"), None);
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{bail, Context};
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use regex::Regex;
+
+use super::{Fields, Record};
+
+/// A format defined in the config, as a regex whose named groups are the fields of the lines
+/// matching it
+#[derive(Debug)]
+pub struct Custom {
+    pub name: String,
+    pattern: Regex,
+    /// how the time field is written, in the syntax of strftime
+    time_format: Option<String>,
+}
+
+impl Custom {
+    pub fn new(name: &str, regex: &str, time_format: Option<&str>) -> anyhow::Result<Self> {
+        let pattern = Regex::new(regex).with_context(|| format!("invalid regex {:?}", regex))?;
+        if pattern.capture_names().flatten().next().is_none() {
+            bail!("the regex of format {:?} has no named groups", name);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            pattern,
+            time_format: time_format.map(String::from),
+        })
+    }
+
+    /// The time is read from the first of the time `fields` the regex captures
+    pub fn parse(&self, text: &str, fields: &Fields) -> Option<Record> {
+        let captures = self.pattern.captures(text)?;
+        let captured: Vec<_> = self
+            .pattern
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), captures.name(name)?.as_str().to_string())))
+            .collect();
+
+        let mut record = Record {
+            fields: captured,
+            time: None,
+        };
+        if let Some(format) = &self.time_format {
+            record.time = record.get(&fields.time).and_then(|t| parse_time(t, format));
+        }
+        Some(record)
+    }
+}
+
+/// Times without a date are taken to be from today
+fn parse_time(time: &str, format: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(time, format)
+        .ok()
+        .or_else(|| {
+            let time = NaiveTime::parse_from_str(time, format).ok()?;
+            Some(Local::now().date_naive().and_time(time))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Custom;
+    use crate::format::Fields;
+    use chrono::{Local, NaiveDate};
+
+    #[test]
+    fn captures_named_groups() {
+        let format = Custom::new(
+            "app",
+            r"^(?P<ts>\d+/\d+/\d+ \S+) \[(?P<level>\w+)\] (?:(?P<thread>[\w-]+): )?(?P<msg>.*)",
+            Some("%d/%m/%Y %H:%M:%S"),
+        )
+        .unwrap();
+
+        let record = format
+            .parse("10/02/2023 13:45:12 [WARN] slow query", &Fields::default())
+            .unwrap();
+        assert_eq!(
+            record.fields,
+            [
+                ("ts".to_string(), "10/02/2023 13:45:12".to_string()),
+                ("level".into(), "WARN".into()),
+                ("msg".into(), "slow query".into()),
+            ]
+        );
+        assert_eq!(
+            record.time,
+            NaiveDate::from_ymd_opt(2023, 2, 10)
+                .unwrap()
+                .and_hms_opt(13, 45, 12)
+        );
+        assert_eq!(format.parse("slow query", &Fields::default()), None);
+    }
+
+    #[test]
+    fn reads_times_without_dates() {
+        let format = Custom::new("app", r"(?P<time>\S+) (?P<msg>.*)", Some("%H:%M:%S")).unwrap();
+
+        let record = format
+            .parse("13:45:12 started", &Fields::default())
+            .unwrap();
+        assert_eq!(
+            record.time,
+            Local::now().date_naive().and_hms_opt(13, 45, 12)
+        );
+    }
+
+    #[test]
+    fn reads_the_configured_time_field() {
+        let format = Custom::new("app", r"(?P<when>\S+) (?P<msg>.*)", Some("%H:%M:%S")).unwrap();
+        let fields = Fields {
+            time: vec!["when".into()],
+            ..Fields::default()
+        };
+
+        assert!(format
+            .parse("13:45:12 started", &fields)
+            .unwrap()
+            .time
+            .is_some());
+        assert_eq!(
+            format
+                .parse("13:45:12 started", &Fields::default())
+                .unwrap()
+                .time,
+            None
+        );
+    }
+
+    #[test]
+    fn needs_named_groups() {
+        assert!(Custom::new("app", r"(\w+) (.*)", None).is_err());
+        assert!(Custom::new("app", r"(?P<msg>", None).is_err());
+    }
+}
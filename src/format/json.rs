@@ -0,0 +1,74 @@
+use serde_json::{Map, Value};
+
+use super::Record;
+
+/// Parses lines holding a JSON object. Nested objects are flattened into dotted names, and other
+/// values that aren't strings are kept as JSON
+pub fn parse(text: &str) -> Option<Record> {
+    let text = text.trim();
+    if !text.starts_with('{') {
+        return None;
+    }
+
+    let object: Map<String, Value> = serde_json::from_str(text).ok()?;
+    let mut fields = vec![];
+    flatten("", object, &mut fields);
+    Some(Record { fields, time: None })
+}
+
+/// Pretty-prints lines holding a JSON object
+pub fn pretty(text: &str) -> Option<String> {
+    let text = text.trim();
+    if !text.starts_with('{') {
+        return None;
+    }
+
+    let value: Value = serde_json::from_str(text).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+fn flatten(prefix: &str, object: Map<String, Value>, fields: &mut Vec<(String, String)>) {
+    for (name, value) in object {
+        let name = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value {
+            Value::Object(object) => flatten(&name, object, fields),
+            Value::String(s) => fields.push((name, s)),
+            value => fields.push((name, value.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn parses_objects_in_order() {
+        let record = parse(
+            r#" {"ts":"2023-02-10T13:45:12Z","level":"warn","http":{"status":503,"path":"/"},"tags":["a"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            record.fields,
+            [
+                ("ts".to_string(), "2023-02-10T13:45:12Z".to_string()),
+                ("level".into(), "warn".into()),
+                ("http.status".into(), "503".into()),
+                ("http.path".into(), "/".into()),
+                ("tags".into(), "[\"a\"]".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse("INFO started"), None);
+        assert_eq!(parse("{not json"), None);
+        assert_eq!(parse("[1, 2]"), None);
+    }
+}
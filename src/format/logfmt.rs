@@ -0,0 +1,83 @@
+use super::Record;
+
+/// Parses lines made of `key=value` pairs, where values with spaces are double-quoted, as in
+/// `time=2023-02-10T13:45:12Z level=info msg="logged in" user=42`. Lines with anything else in
+/// them aren't logfmt
+pub fn parse(text: &str) -> Option<Record> {
+    let mut fields = vec![];
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        let (name, after) = rest.split_once('=')?;
+        if name.is_empty() || name.contains([' ', '"']) {
+            return None;
+        }
+
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => unquote(quoted)?,
+            None => match after.find(' ') {
+                Some(end) => (after[..end].to_string(), &after[end..]),
+                None => (after.to_string(), ""),
+            },
+        };
+        // values end where the next pair starts
+        if !after.is_empty() && !after.starts_with(' ') {
+            return None;
+        }
+
+        fields.push((name.to_string(), value));
+        rest = after.trim_start();
+    }
+
+    (!fields.is_empty()).then_some(Record { fields, time: None })
+}
+
+/// Reads a quoted value up to its closing quote, returning it and what comes after
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn parses_pairs() {
+        let record =
+            parse(r#"time=2023-02-10T13:45:12Z level=info msg="said \"hi\"" user=42 empty="""#)
+                .unwrap();
+
+        assert_eq!(
+            record.fields,
+            [
+                ("time".to_string(), "2023-02-10T13:45:12Z".to_string()),
+                ("level".into(), "info".into()),
+                ("msg".into(), "said \"hi\"".into()),
+                ("user".into(), "42".into()),
+                ("empty".into(), "".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse("INFO user=42 logged in"), None);
+        assert_eq!(parse("msg=\"unterminated"), None);
+        assert_eq!(parse("a=\"b\"c"), None);
+        assert_eq!(parse(""), None);
+    }
+}
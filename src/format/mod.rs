@@ -0,0 +1,446 @@
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use std::sync::Arc;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::{
+    group::Grouping,
+    line::{self, Line},
+    scope::FileScope,
+};
+
+use custom::Custom;
+
+pub mod access;
+pub mod custom;
+pub mod json;
+pub mod logfmt;
+pub mod syslog;
+
+/// Formats lines can be in
+#[derive(Clone, Debug)]
+pub enum Format {
+    /// lines that are never parsed
+    Plain,
+    Json,
+    Logfmt,
+    Syslog,
+    /// web server access logs
+    Access,
+    /// defined in the config
+    Custom(Arc<Custom>),
+}
+
+impl Format {
+    /// built-in formats tried on the lines of sources that weren't given one, before the custom
+    /// ones
+    const DETECTED: [Format; 4] = [Format::Json, Format::Logfmt, Format::Syslog, Format::Access];
+
+    pub fn from_name(name: &str, customs: &[Arc<Custom>]) -> Option<Self> {
+        match name {
+            "plain" => Some(Format::Plain),
+            "json" => Some(Format::Json),
+            "logfmt" => Some(Format::Logfmt),
+            "syslog" => Some(Format::Syslog),
+            "access" => Some(Format::Access),
+            name => customs
+                .iter()
+                .find(|c| c.name == name)
+                .map(|c| Format::Custom(c.clone())),
+        }
+    }
+
+    /// Splits a line into fields. `fields` names the ones with a meaning, like the time
+    pub fn parse(&self, text: &str, fields: &Fields) -> Option<Record> {
+        match self {
+            Format::Plain => None,
+            Format::Json => json::parse(text),
+            Format::Logfmt => logfmt::parse(text),
+            Format::Syslog => syslog::parse(text),
+            Format::Access => access::parse(text),
+            Format::Custom(custom) => custom.parse(text, fields),
+        }
+    }
+}
+
+/// A format forced on the sources it applies to, rather than detected
+#[derive(Clone, Debug)]
+pub struct FormatRule {
+    pub format: Format,
+    pub files: FileScope,
+}
+
+impl FormatRule {
+    /// Parses rules given as `FORMAT` or `FILES=FORMAT`
+    pub fn parse(spec: &str, customs: &[Arc<Custom>]) -> anyhow::Result<Self> {
+        match spec.rsplit_once('=') {
+            Some((files, name)) => Self::new(name, FileScope::new(files)?, customs),
+            None => Self::new(spec, FileScope::default(), customs),
+        }
+    }
+
+    pub fn new(name: &str, files: FileScope, customs: &[Arc<Custom>]) -> anyhow::Result<Self> {
+        let format = Format::from_name(name, customs).ok_or_else(|| {
+            let mut names = vec!["plain", "json", "logfmt", "syslog", "access"];
+            names.extend(customs.iter().map(|c| c.name.as_str()));
+            anyhow!(
+                "unknown format {:?}, expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+
+        Ok(Self { format, files })
+    }
+}
+
+/// Turns the lines of a source into records. Unless it's given a format, the source is taken to
+/// be in the format of the first of its lines that is in one
+#[derive(Clone, Debug, Default)]
+pub struct Parser {
+    format: Option<Format>,
+    /// custom formats to try along with the built-in ones
+    customs: Vec<Arc<Custom>>,
+    grouping: Grouping,
+    /// names of the fields records are rendered from
+    fields: Fields,
+}
+
+/// What a parser makes of a line
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub record: Option<Record>,
+    /// whether the line continues the one before it, like the frames of a stack trace
+    pub continued: bool,
+    /// the record rendered the way panes show it, so it's only ever done once
+    pub pretty: Option<Spans<'static>>,
+    /// time of the record, from its time field
+    pub time: Option<NaiveDateTime>,
+}
+
+impl Parser {
+    pub fn new(
+        format: Option<Format>,
+        customs: &[Arc<Custom>],
+        grouping: Grouping,
+        fields: &Fields,
+    ) -> Self {
+        Self {
+            format,
+            customs: customs.to_vec(),
+            grouping,
+            fields: fields.clone(),
+        }
+    }
+
+    /// Lines continuing the one before them aren't parsed, as they're in no format of their own
+    pub fn parse(&mut self, text: &str) -> Parsed {
+        if self.grouping.continues(text) {
+            return Parsed {
+                continued: true,
+                ..Parsed::default()
+            };
+        }
+        let record = self.record(text);
+        Parsed {
+            pretty: record.as_ref().map(|r| r.pretty(&self.fields)),
+            time: record.as_ref().and_then(|r| {
+                r.time
+                    .or_else(|| r.get(&self.fields.time).and_then(line::leading_timestamp))
+            }),
+            record,
+            continued: false,
+        }
+    }
+
+    fn record(&mut self, text: &str) -> Option<Record> {
+        if let Some(format) = &self.format {
+            return format.parse(text, &self.fields);
+        }
+
+        let customs = self.customs.iter().map(|c| Format::Custom(c.clone()));
+        let (format, record) = Format::DETECTED
+            .into_iter()
+            .chain(customs)
+            .find_map(|format| {
+                let record = format.parse(text, &self.fields)?;
+                // a single key=value is as likely to be part of a sentence
+                if matches!(format, Format::Logfmt) && record.fields.len() < 2 {
+                    return None;
+                }
+                Some((format, record))
+            })?;
+        self.format = Some(format);
+        Some(record)
+    }
+}
+
+/// A line split into named fields by one of the formats
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// fields in the order they appear in the line
+    pub fields: Vec<(String, String)>,
+    /// time of the line, for formats that know how their time field is written
+    pub time: Option<NaiveDateTime>,
+}
+
+/// Names of the fields holding the time, level and message of records. The first one found is
+/// used
+#[derive(Clone, Debug)]
+pub struct Fields {
+    pub time: Vec<String>,
+    pub level: Vec<String>,
+    pub message: Vec<String>,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        Self {
+            time: names(&["time", "timestamp", "ts", "@timestamp", "t"]),
+            level: names(&["level", "lvl", "severity", "loglevel"]),
+            message: names(&["msg", "message", "@message", "text"]),
+        }
+    }
+}
+
+/// A line as a pane shows it: rendered from its record, unless the pane shows lines as read
+pub struct Shown<'a> {
+    /// index of the source of the line
+    pub source: usize,
+    pub line: &'a Line,
+    /// lines of the record folded under this one
+    pub folded: usize,
+    raw: bool,
+}
+
+impl<'a> Shown<'a> {
+    pub fn new(source: usize, line: &'a Line, raw: bool) -> Self {
+        Self {
+            source,
+            line,
+            folded: 0,
+            raw,
+        }
+    }
+
+    pub fn spans(&self) -> &Spans<'static> {
+        match &self.line.pretty {
+            Some((spans, _)) if !self.raw => spans,
+            _ => &self.line.spans,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match &self.line.pretty {
+            Some((_, text)) if !self.raw => text,
+            _ => &self.line.text,
+        }
+    }
+}
+
+impl Record {
+    /// Value of the first of the given fields the record has
+    pub fn get<S: AsRef<str>>(&self, names: &[S]) -> Option<&str> {
+        self.find(names).map(|i| self.fields[i].1.as_str())
+    }
+
+    fn find<S: AsRef<str>>(&self, names: &[S]) -> Option<usize> {
+        names
+            .iter()
+            .find_map(|n| self.fields.iter().position(|(name, _)| name == n.as_ref()))
+    }
+
+    /// Renders the record as `time level message key=value...`. Requests, which have no level,
+    /// show their status in its place
+    pub fn pretty(&self, names: &Fields) -> Spans<'static> {
+        let (time, level, message) = (
+            self.find(&names.time),
+            self.find(&names.level),
+            self.find(&names.message),
+        );
+        let status = self.find(&["status"]).filter(|_| level.is_none());
+
+        let mut spans = vec![];
+        if let Some(i) = time {
+            spans.push(Span::styled(
+                format!("{} ", self.fields[i].1),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if let Some(i) = level {
+            let level = &self.fields[i].1;
+            spans.push(Span::styled(
+                format!("{:<5} ", level.to_uppercase()),
+                level_style(level),
+            ));
+        }
+        if let Some(i) = status {
+            let status = &self.fields[i].1;
+            spans.push(Span::styled(
+                format!("{:<5} ", status),
+                status_style(status),
+            ));
+        }
+        if let Some(i) = message {
+            spans.push(Span::raw(self.fields[i].1.clone()));
+        }
+
+        let shown = [time, level, status, message];
+        let rest = (0..self.fields.len()).filter(|i| !shown.contains(&Some(*i)));
+        for i in rest {
+            let (name, value) = &self.fields[i];
+            if spans.last().is_some_and(|s| !s.content.ends_with(' ')) {
+                spans.push(Span::raw(" "));
+            }
+            spans.push(Span::styled(
+                format!("{}=", name),
+                Style::default().fg(Color::Cyan),
+            ));
+            spans.push(Span::raw(quote(value)));
+        }
+
+        Spans::from(spans)
+    }
+}
+
+#[cfg(test)]
+impl Record {
+    /// A record with the given fields and no time
+    pub fn from_pairs(fields: &[(&str, &str)]) -> Self {
+        Self {
+            fields: fields
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            time: None,
+        }
+    }
+}
+
+/// Colors levels by how bad they are
+pub fn level_style(level: &str) -> Style {
+    let style = Style::default().add_modifier(Modifier::BOLD);
+    match level.to_lowercase().as_str() {
+        "fatal" | "panic" | "crit" | "critical" | "alert" | "emerg" | "emergency" => {
+            style.fg(Color::White).bg(Color::Red)
+        }
+        "error" | "err" | "eror" => style.fg(Color::Red),
+        "warn" | "warning" | "wrn" => style.fg(Color::Yellow),
+        "info" | "inf" | "notice" => style.fg(Color::Green),
+        "debug" | "dbg" => style.fg(Color::Blue),
+        "trace" | "trc" => style.fg(Color::DarkGray),
+        _ => style,
+    }
+}
+
+/// Colors HTTP statuses by class
+pub fn status_style(status: &str) -> Style {
+    let style = Style::default().add_modifier(Modifier::BOLD);
+    match status.as_bytes().first() {
+        Some(b'2') => style.fg(Color::Green),
+        Some(b'3') => style.fg(Color::Cyan),
+        Some(b'4') => style.fg(Color::Yellow),
+        Some(b'5') => style.fg(Color::Red),
+        _ => style,
+    }
+}
+
+/// Quotes values that wouldn't read as a single one otherwise
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '"', '=']) {
+        format!("{:?}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fields, Format, Parser, Record};
+    use crate::group::Grouping;
+
+    fn text(record: &Record, fields: &Fields) -> String {
+        let spans = record.pretty(fields);
+        spans.0.iter().map(|s| s.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn renders_known_fields_first() {
+        let record = Record::from_pairs(&[
+            ("user", "ada"),
+            ("msg", "logged in"),
+            ("level", "info"),
+            ("ts", "2023-02-10T13:45:12Z"),
+            ("agent", "curl 7.0"),
+        ]);
+
+        assert_eq!(
+            text(&record, &Fields::default()),
+            "2023-02-10T13:45:12Z INFO  logged in user=ada agent=\"curl 7.0\""
+        );
+    }
+
+    #[test]
+    fn shows_the_status_of_requests_as_their_level() {
+        let record = Record::from_pairs(&[
+            ("time", "10/Feb/2023:13:45:12 +0000"),
+            ("method", "GET"),
+            ("path", "/"),
+            ("status", "503"),
+        ]);
+
+        assert_eq!(
+            text(&record, &Fields::default()),
+            "10/Feb/2023:13:45:12 +0000 503   method=GET path=/"
+        );
+    }
+
+    #[test]
+    fn detects_the_format_of_sources() {
+        let mut parser = Parser::default();
+        assert_eq!(parser.parse("starting up, debug=true").record, None);
+        // lines continuing others have no format of their own
+        let parsed = parser.parse("  level=info msg=started");
+        assert!(parsed.continued && parsed.record.is_none());
+        assert!(parser.parse("level=info msg=started").record.is_some());
+        // not json anymore, now that the source is known to be logfmt
+        assert_eq!(parser.parse(r#"{"level":"info"}"#).record, None);
+        assert!(parser.parse("msg=stopped").record.is_some());
+
+        let mut parser = Parser::new(
+            Some(Format::Plain),
+            &[],
+            Grouping::default(),
+            &Fields::default(),
+        );
+        assert_eq!(parser.parse("level=info msg=started").record, None);
+    }
+
+    #[test]
+    fn uses_configured_fields() {
+        let record = Record::from_pairs(&[("message", "hello"), ("body", "logged in")]);
+        let fields = Fields {
+            message: vec!["body".into()],
+            ..Fields::default()
+        };
+
+        assert_eq!(text(&record, &fields), "logged in message=hello");
+    }
+
+    #[test]
+    fn takes_the_time_from_configured_fields() {
+        let line = "when=2023-02-10T13:45:12Z ts=2020-01-01T00:00:00Z msg=hi";
+        let fields = Fields {
+            time: vec!["when".into()],
+            ..Fields::default()
+        };
+        let mut parser = Parser::new(None, &[], Grouping::default(), &fields);
+
+        let time = parser.parse(line).time.unwrap();
+        assert_eq!(time.to_string(), "2023-02-10 13:45:12");
+    }
+}
@@ -0,0 +1,299 @@
+use super::Record;
+
+/// Severities, by the value they have in priorities
+const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses syslog lines, either as sent over the network with a priority, or as written to files
+/// like `/var/log/syslog` without one:
+///
+/// - RFC 5424: `<34>1 2023-02-10T13:45:12.003Z host app 123 ID47 [id key="value"] message`
+/// - RFC 3164: `<34>Feb 10 13:45:12 host app[123]: message`, also with the ISO timestamps of
+///   rsyslog
+pub fn parse(text: &str) -> Option<Record> {
+    let (priority, rest) = match text.strip_prefix('<') {
+        Some(rest) => {
+            let (priority, rest) = rest.split_once('>')?;
+            (
+                Some(priority.parse::<u8>().ok().filter(|&p| p < 192)?),
+                rest,
+            )
+        }
+        None => (None, text),
+    };
+
+    let mut fields = match rest.strip_prefix("1 ") {
+        Some(rest) if priority.is_some() => rfc5424(rest)?,
+        _ => rfc3164(rest, priority.is_some())?,
+    };
+
+    if let Some(priority) = priority {
+        let (facility, severity) = (priority / 8, priority % 8);
+        // after the time, host, app and such, but before the message
+        let at = fields.len() - 1;
+        fields.insert(
+            at,
+            ("facility".into(), FACILITIES[facility as usize].into()),
+        );
+        fields.insert(
+            at + 1,
+            ("severity".into(), SEVERITIES[severity as usize].into()),
+        );
+    }
+    Some(Record { fields, time: None })
+}
+
+type Fields = Vec<(String, String)>;
+
+fn rfc5424(text: &str) -> Option<Fields> {
+    let mut parts = text.splitn(6, ' ');
+    let mut next = || parts.next().filter(|p| !p.is_empty());
+    let (time, host, app, pid, msgid) = (next()?, next()?, next()?, next()?, next()?);
+    let rest = parts.next().unwrap_or("");
+    if time != "-" && time.as_bytes().get(4) != Some(&b'-') {
+        return None;
+    }
+
+    let mut fields = vec![];
+    let header = [
+        ("time", time),
+        ("host", host),
+        ("app", app),
+        ("pid", pid),
+        ("msgid", msgid),
+    ];
+    for (name, value) in header {
+        if value != "-" {
+            fields.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    let message = match rest.strip_prefix('-') {
+        Some(message) => message,
+        None => structured_data(rest, &mut fields)?,
+    };
+    let message = message.strip_prefix(' ').unwrap_or(message);
+    fields.push(("msg".into(), message.trim_start_matches('\u{feff}').into()));
+    Some(fields)
+}
+
+/// Reads elements like `[id key="value" ...]` as `id.key` fields, returning what comes after them
+fn structured_data<'a>(mut text: &'a str, fields: &mut Fields) -> Option<&'a str> {
+    if !text.starts_with('[') {
+        return None;
+    }
+
+    while let Some(element) = text.strip_prefix('[') {
+        let end = element.find([' ', ']'])?;
+        let id = &element[..end];
+        let mut rest = &element[end..];
+
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if let Some(after) = rest.strip_prefix(']') {
+                text = after;
+                break;
+            }
+            let (name, after) = rest.split_once("=\"")?;
+            let (value, after) = unescape(after)?;
+            fields.push((format!("{}.{}", id, name), value));
+            rest = after;
+        }
+    }
+    Some(text)
+}
+
+/// Reads a parameter value up to its closing quote, where `"`, `\` and `]` are escaped
+fn unescape(text: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[i + 1..])),
+            '\\' => {
+                let (_, c) = chars.next()?;
+                if !matches!(c, '"' | '\\' | ']') {
+                    value.push('\\');
+                }
+                value.push(c);
+            }
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn rfc3164(text: &str, prioritized: bool) -> Option<Fields> {
+    let (time, rest) = bsd_timestamp(text).or_else(|| iso_timestamp(text))?;
+    let (host, rest) = rest.split_once(' ')?;
+    // without a priority to tell, lines starting with a timestamp and a level, like
+    // `2023-02-10T13:45:12Z INFO main: started`, could pass for syslog too
+    if host.is_empty() || !prioritized && host.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+
+    let (tag, message) = rest.split_once(':')?;
+    if !(message.is_empty() || message.starts_with(' ')) {
+        return None;
+    }
+    let (app, pid) = match tag.strip_suffix(']') {
+        Some(tag) => {
+            let (app, pid) = tag.split_once('[')?;
+            (app, Some(pid))
+        }
+        None => (tag, None),
+    };
+    if app.is_empty() || app.contains([' ', '[']) {
+        return None;
+    }
+
+    let mut fields = vec![
+        ("time".to_string(), time.to_string()),
+        ("host".into(), host.into()),
+        ("app".into(), app.into()),
+    ];
+    if let Some(pid) = pid {
+        fields.push(("pid".into(), pid.into()));
+    }
+    fields.push(("msg".into(), message.trim_start().into()));
+    Some(fields)
+}
+
+/// `Feb 10 13:45:12`, with days below 10 padded with a space
+fn bsd_timestamp(text: &str) -> Option<(&str, &str)> {
+    let head = text.get(..15)?;
+    let bytes = head.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+
+    let valid = MONTHS.contains(&head.get(..3)?)
+        && bytes[3] == b' '
+        && (bytes[4] == b' ' || digits(4..5))
+        && digits(5..6)
+        && bytes[6] == b' '
+        && digits(7..9)
+        && bytes[9] == b':'
+        && digits(10..12)
+        && bytes[12] == b':'
+        && digits(13..15);
+    let rest = text[15..].strip_prefix(' ')?;
+    valid.then_some((head, rest))
+}
+
+/// `2023-02-10T13:45:12.003+01:00`, which rsyslog writes. The timezone is required, as it's what
+/// tells it apart from the timestamps of other logs
+fn iso_timestamp(text: &str) -> Option<(&str, &str)> {
+    let (time, rest) = text.split_once(' ')?;
+    let bytes = time.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[10] != b'T' {
+        return None;
+    }
+
+    let zoned = time.ends_with('Z') || matches!(bytes[bytes.len() - 6], b'+' | b'-');
+    zoned.then_some((time, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::format::Record;
+
+    #[test]
+    fn parses_rfc5424() {
+        assert_eq!(
+            parse(
+                r#"<165>1 2023-02-10T13:45:12.003Z web-1 api 812 ID47 [req@123 id="a\"b" user="ada"][x@1 k="v"] started"#
+            ),
+            Some(Record::from_pairs(&[
+                ("time", "2023-02-10T13:45:12.003Z"),
+                ("host", "web-1"),
+                ("app", "api"),
+                ("pid", "812"),
+                ("msgid", "ID47"),
+                ("req@123.id", "a\"b"),
+                ("req@123.user", "ada"),
+                ("x@1.k", "v"),
+                ("facility", "local4"),
+                ("severity", "notice"),
+                ("msg", "started"),
+            ]))
+        );
+        assert_eq!(
+            parse("<11>1 2023-02-10T13:45:12Z web-1 api - - - disk full"),
+            Some(Record::from_pairs(&[
+                ("time", "2023-02-10T13:45:12Z"),
+                ("host", "web-1"),
+                ("app", "api"),
+                ("facility", "user"),
+                ("severity", "err"),
+                ("msg", "disk full"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_rfc3164() {
+        assert_eq!(
+            parse("<34>Feb  1 13:45:12 web-1 sshd[4242]: Accepted publickey for ada"),
+            Some(Record::from_pairs(&[
+                ("time", "Feb  1 13:45:12"),
+                ("host", "web-1"),
+                ("app", "sshd"),
+                ("pid", "4242"),
+                ("facility", "auth"),
+                ("severity", "crit"),
+                ("msg", "Accepted publickey for ada"),
+            ]))
+        );
+        assert_eq!(
+            parse("2023-02-10T13:45:12.003512+01:00 web-1 kernel: usb 1-1: new device"),
+            Some(Record::from_pairs(&[
+                ("time", "2023-02-10T13:45:12.003512+01:00"),
+                ("host", "web-1"),
+                ("app", "kernel"),
+                ("msg", "usb 1-1: new device"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse("2023-02-10T13:45:12Z INFO main: started"), None);
+        assert_eq!(parse("2023-02-10 13:45:12 web-1 api: started"), None);
+        assert_eq!(parse("Feb 10 13:45:12 web-1 no tag here"), None);
+        assert_eq!(parse("<999>1 2023-02-10T13:45:12Z web-1 api - - - x"), None);
+        assert_eq!(parse("level=info msg=started"), None);
+    }
+}
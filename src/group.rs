@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use regex::Regex;
+
+use crate::{
+    filter::Filters,
+    format::{Record, Shown},
+    scope::FileScope,
+    view::View,
+};
+
+/// Lines that continue the record before them unless configured otherwise: indented ones, like
+/// the frames of stack traces, and the causes of Java exceptions
+const CONTINUATION: &str = r"^(\s+\S|Caused by: )";
+
+/// How lines are grouped into records, which are filtered and folded as a whole
+#[derive(Clone, Debug)]
+pub enum Grouping {
+    /// lines matching the regex continue the record before them
+    Continuation(Regex),
+    /// lines matching the regex start a record, and the others continue the one before them
+    Start(Regex),
+}
+
+impl Default for Grouping {
+    fn default() -> Self {
+        Grouping::Continuation(Regex::new(CONTINUATION).unwrap())
+    }
+}
+
+impl Grouping {
+    /// Whether a line continues the record before it
+    pub fn continues(&self, text: &str) -> bool {
+        match self {
+            Grouping::Continuation(pattern) => pattern.is_match(text),
+            Grouping::Start(pattern) => !pattern.is_match(text),
+        }
+    }
+}
+
+/// A grouping used for the sources it applies to
+#[derive(Clone, Debug)]
+pub struct GroupRule {
+    pub grouping: Grouping,
+    pub files: FileScope,
+}
+
+impl GroupRule {
+    /// Rules have either a regex for the lines starting records or one for the lines continuing
+    /// them
+    pub fn new(
+        start: Option<&str>,
+        continuation: Option<&str>,
+        files: FileScope,
+    ) -> anyhow::Result<Self> {
+        let regex =
+            |regex: &str| Regex::new(regex).with_context(|| format!("invalid regex {:?}", regex));
+        let grouping = match (start, continuation) {
+            (Some(start), None) => Grouping::Start(regex(start)?),
+            (None, Some(continuation)) => Grouping::Continuation(regex(continuation)?),
+            _ => bail!("expected either a start or a continuation regex"),
+        };
+
+        Ok(Self { grouping, files })
+    }
+}
+
+/// Keeps the lines of the records the filters keep, out of lines given newest first. A record
+/// matches a filter when any of its lines does, with the fields of its first line. Also returns
+/// how many lines were hidden
+pub fn keep<'a>(lines: Vec<Shown<'a>>, filters: &Filters) -> (Vec<Shown<'a>>, usize) {
+    if filters.is_empty() || filters.disabled {
+        return (lines, 0);
+    }
+
+    // whether each record matched each filter, flattened so that it's only allocated once
+    let n = filters.iter().count();
+    let mut records: HashMap<u64, usize> = HashMap::new();
+    let mut heads: Vec<Option<&Record>> = vec![];
+    let mut matched: Vec<bool> = vec![];
+    // oldest first, so that the first line of a record comes before the ones continuing it
+    for line in lines.iter().rev() {
+        let i = *records.entry(line.line.group).or_insert_with(|| {
+            heads.push(None);
+            matched.resize(matched.len() + n, false);
+            heads.len() - 1
+        });
+        if line.line.seq == line.line.group {
+            heads[i] = line.line.record.as_ref();
+        }
+        for (filter, matched) in filters.iter().zip(&mut matched[i * n..(i + 1) * n]) {
+            *matched = *matched || filter.matches(line.text(), heads[i]);
+        }
+    }
+    let kept = |group: u64| {
+        let i = records[&group];
+        filters.keeps_matching(|j| matched[i * n + j])
+    };
+
+    let (lines, hidden): (Vec<_>, Vec<_>) = lines.into_iter().partition(|l| kept(l.line.group));
+    (lines, hidden.len())
+}
+
+/// Folds the records the view has folded down to their first line, which tells how many lines it
+/// stands for
+pub fn fold<'a>(lines: Vec<Shown<'a>>, view: &View) -> Vec<Shown<'a>> {
+    // the first line of a record may be gone from the history, and the oldest one left stands in
+    let mut records: HashMap<u64, (usize, u64)> = HashMap::new();
+    for line in &lines {
+        let (len, first) = records.entry(line.line.group).or_insert((0, u64::MAX));
+        *len += 1;
+        *first = line.line.seq.min(*first);
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|mut line| {
+            let (len, first) = records[&line.line.group];
+            if !view.is_folded(line.line.group, len) {
+                Some(line)
+            } else if line.line.seq == first {
+                line.folded = len - 1;
+                Some(line)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold, keep, GroupRule, Grouping};
+    use crate::scope::FileScope;
+    use crate::{
+        filter::{Filter, Filters},
+        format::{Record, Shown},
+        line::Line,
+        view::View,
+    };
+    use tui::text::Spans;
+
+    // lines of records given oldest first, each record as its lines
+    fn lines(records: &[&[&str]]) -> Vec<Line> {
+        let mut lines = vec![];
+        for record in records {
+            let mut group = None;
+            for text in record.iter() {
+                let mut line = Line::new(Spans::from(text.to_string()));
+                line.group = *group.get_or_insert(line.seq);
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    fn shown(lines: &[Line]) -> Vec<Shown<'_>> {
+        lines
+            .iter()
+            .rev()
+            .map(|l| Shown::new(0, l, false))
+            .collect()
+    }
+
+    fn texts(lines: &[Shown]) -> Vec<String> {
+        lines.iter().rev().map(|l| l.text().to_string()).collect()
+    }
+
+    #[test]
+    fn groups_lines() {
+        let grouping = Grouping::default();
+        assert!(!grouping.continues("ERROR request failed"));
+        assert!(grouping.continues("\tat com.example.Main.run(Main.java:12)"));
+        assert!(grouping.continues("Caused by: java.io.IOException"));
+        assert!(!grouping.continues("   "));
+
+        let rule = GroupRule::new(Some(r"^\d{4}-"), None, FileScope::default()).unwrap();
+        assert!(!rule.grouping.continues("2023-02-10 ERROR failed"));
+        assert!(rule
+            .grouping
+            .continues("Traceback (most recent call last):"));
+
+        assert!(GroupRule::new(None, None, FileScope::default()).is_err());
+        assert!(GroupRule::new(Some("a"), Some("b"), FileScope::default()).is_err());
+    }
+
+    #[test]
+    fn filters_whole_records() {
+        let lines = lines(&[
+            &["INFO started"],
+            &["ERROR request failed", "  at handler", "  at main"],
+            &["INFO stopped"],
+        ]);
+        let mut filters = Filters::default();
+        filters.push(Filter::parse("ERROR", false, false).unwrap());
+
+        let (kept, hidden) = keep(shown(&lines), &filters);
+        assert_eq!(
+            texts(&kept),
+            ["ERROR request failed", "  at handler", "  at main"]
+        );
+        assert_eq!(hidden, 2);
+
+        let mut filters = Filters::default();
+        filters.push(Filter::parse("handler", false, true).unwrap());
+        let (kept, _) = keep(shown(&lines), &filters);
+        assert_eq!(texts(&kept), ["INFO started", "INFO stopped"]);
+    }
+
+    #[test]
+    fn filters_records_by_the_fields_of_their_first_line() {
+        let mut lines = lines(&[
+            &["level=error msg=failed", "  at handler"],
+            &["level=info msg=ok"],
+        ]);
+        lines[0].record = Some(Record::from_pairs(&[("level", "error"), ("msg", "failed")]));
+        lines[2].record = Some(Record::from_pairs(&[("level", "info"), ("msg", "ok")]));
+        let mut filters = Filters::default();
+        filters.push(Filter::parse("level=error", false, false).unwrap());
+
+        let (kept, hidden) = keep(shown(&lines), &filters);
+        assert_eq!(texts(&kept), ["level=error msg=failed", "  at handler"]);
+        assert_eq!(hidden, 1);
+    }
+
+    #[test]
+    fn folds_long_records() {
+        let trace: Vec<_> = std::iter::once("ERROR panicked")
+            .chain(["  at frame"; 10])
+            .collect();
+        let lines = lines(&[&["INFO started"], &trace, &["  stray"]]);
+        let mut view = View::default();
+
+        // shown in full until folded
+        assert_eq!(fold(shown(&lines), &view).len(), 13);
+
+        view.toggle_fold(lines[1].group);
+        let folded = fold(shown(&lines), &view);
+        assert_eq!(
+            texts(&folded),
+            ["INFO started", "ERROR panicked", "  stray"]
+        );
+        assert_eq!(folded[1].folded, 10);
+
+        view.toggle_folds();
+        assert_eq!(fold(shown(&lines), &view).len(), 3);
+        view.toggle_fold(lines[1].group);
+        assert_eq!(fold(shown(&lines), &view).len(), 13);
+        view.toggle_folds();
+        assert_eq!(fold(shown(&lines), &view).len(), 13);
+    }
+}
@@ -0,0 +1,173 @@
+use std::ops::Range;
+
+use anyhow::{anyhow, bail, Context};
+use regex::Regex;
+use tui::style::{Color, Modifier, Style};
+
+use crate::scope::FileScope;
+
+/// A style applied to the parts of lines matching a regex, or to whole lines
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub pattern: Regex,
+    pub style: Style,
+    /// whether the style covers the whole line rather than just the match
+    pub line: bool,
+    pub files: FileScope,
+}
+
+impl Rule {
+    /// Parses rules given as `REGEX=STYLE`, where the style is a comma separated list of colors
+    /// and modifiers (see `parse_style`), optionally including `line`
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (regex, style) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("expected REGEX=STYLE, got {:?}", spec))?;
+
+        let line = style.split(',').any(|token| token.trim() == "line");
+        let style: Vec<_> = style
+            .split(',')
+            .filter(|token| token.trim() != "line")
+            .collect();
+
+        Self::new(regex, &style.join(","), line, FileScope::default())
+    }
+
+    pub fn new(regex: &str, style: &str, line: bool, files: FileScope) -> anyhow::Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(regex).with_context(|| format!("invalid regex {:?}", regex))?,
+            style: parse_style(style)?,
+            line,
+            files,
+        })
+    }
+}
+
+/// Styles to layer on top of a line, in the order they should be applied. Whole line styles come
+/// first, so the ones of matches show over them
+pub fn ranges(rules: &[Rule], text: &str) -> Vec<(Range<usize>, Style)> {
+    let lines = rules
+        .iter()
+        .filter(|r| r.line && r.pattern.is_match(text))
+        .map(|r| (0..text.len(), r.style));
+    let matches = rules
+        .iter()
+        .filter(|r| !r.line)
+        .flat_map(|r| r.pattern.find_iter(text).map(|m| (m.range(), r.style)));
+
+    lines.chain(matches).collect()
+}
+
+/// Parses styles like `red,bold`, `bright-yellow on blue` or `underline`. Tokens can be separated
+/// by commas or spaces, and colors given by name or as `#rrggbb`
+pub fn parse_style(spec: &str) -> anyhow::Result<Style> {
+    let mut style = Style::default();
+    let mut tokens = spec
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+
+    while let Some(token) = tokens.next() {
+        style = match token.to_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underline" | "underlined" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" | "reverse" => style.add_modifier(Modifier::REVERSED),
+            "strikethrough" | "crossed" => style.add_modifier(Modifier::CROSSED_OUT),
+            "blink" => style.add_modifier(Modifier::SLOW_BLINK),
+            "on" => {
+                let color = tokens
+                    .next()
+                    .ok_or_else(|| anyhow!("expected a color after \"on\" in {:?}", spec))?;
+                style.bg(parse_color(color)?)
+            }
+            color => style.fg(parse_color(color)?),
+        };
+    }
+
+    Ok(style)
+}
+
+fn parse_color(name: &str) -> anyhow::Result<Color> {
+    let name = name.to_lowercase().replace(['-', '_'], "");
+
+    if let Some(hex) = name.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6);
+        return match rgb {
+            Some(rgb) => Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+            None => bail!("invalid color #{}", hex),
+        };
+    }
+
+    Ok(match name.as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "brightred" | "lightred" => Color::LightRed,
+        "brightgreen" | "lightgreen" => Color::LightGreen,
+        "brightyellow" | "lightyellow" => Color::LightYellow,
+        "brightblue" | "lightblue" => Color::LightBlue,
+        "brightmagenta" | "lightmagenta" => Color::LightMagenta,
+        "brightcyan" | "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => bail!("unknown color or modifier {:?}", name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_style, ranges, Rule};
+    use tui::style::{Color, Modifier, Style};
+
+    #[test]
+    fn parses_styles() {
+        assert_eq!(
+            parse_style("red,bold").unwrap(),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(
+            parse_style("bright-yellow on #102030").unwrap(),
+            Style::default()
+                .fg(Color::LightYellow)
+                .bg(Color::Rgb(0x10, 0x20, 0x30))
+        );
+        assert!(parse_style("reddish").is_err());
+        assert!(parse_style("on").is_err());
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule = Rule::parse("level=(ERROR|FATAL)=red,line").unwrap();
+        assert_eq!(rule.pattern.as_str(), "level=(ERROR|FATAL)");
+        assert_eq!(rule.style, Style::default().fg(Color::Red));
+        assert!(rule.line);
+
+        assert!(Rule::parse("req-[0-9a-f]+").is_err());
+        assert!(Rule::parse("req-[0-9a-f+=underline").is_err());
+    }
+
+    #[test]
+    fn layers_matches_over_lines() {
+        let rules = [
+            Rule::parse("req-[0-9a-f]+=underline").unwrap(),
+            Rule::parse("ERROR=red,line").unwrap(),
+            Rule::parse("health=dim,line").unwrap(),
+        ];
+
+        let text = "ERROR req-1f failed";
+        assert_eq!(
+            ranges(&rules, text),
+            vec![
+                (0..19, Style::default().fg(Color::Red)),
+                (6..12, Style::default().add_modifier(Modifier::UNDERLINED)),
+            ]
+        );
+    }
+}
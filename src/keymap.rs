@@ -0,0 +1,669 @@
+use std::fmt;
+
+use anyhow::{anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// What keys are currently for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    /// typing a search or a filter
+    Search,
+    /// typing a `:` command
+    Command,
+    /// moving a cursor over the lines of a pane
+    Select,
+    /// looking at the help
+    Help,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Normal,
+        Mode::Search,
+        Mode::Command,
+        Mode::Select,
+        Mode::Help,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Search => "search",
+            Mode::Command => "command",
+            Mode::Select => "select",
+            Mode::Help => "help",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UIAction {
+    Quit,
+    SwitchTabs(usize),
+    CycleLayout,
+    ToggleMerged,
+    FocusNext,
+    FocusPrev,
+    Restart,
+    Kill,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollTop,
+    Follow,
+    TogglePause,
+    Search,
+    NextMatch,
+    PrevMatch,
+    ClearSearch,
+    Clear,
+    Filter,
+    Exclude,
+    ToggleFilters,
+    PopFilter,
+    ToggleRaw,
+    FilterApp,
+    ExcludeApp,
+    Fold,
+    FoldAll,
+    Select,
+    ToggleDetail,
+    DetailUp,
+    DetailDown,
+    Command,
+    Help,
+    PromptType(char),
+    PromptErase,
+    PromptToggleRegex,
+    PromptComplete,
+    PromptSubmit,
+    PromptCancel,
+    MouseScroll { up: bool, column: u16, row: u16 },
+    Noop,
+}
+
+/// number of tabs that have an action switching to them
+const TABS: usize = 9;
+
+/// Every action that can be bound to keys or run as a command, by name, along with what it does.
+/// Switching tabs stands for one action per tab, named `tab-1` to `tab-9`
+const ACTIONS: &[(&str, UIAction, &str)] = &[
+    ("quit", UIAction::Quit, "quit"),
+    (
+        "tab",
+        UIAction::SwitchTabs(0),
+        "switch tabs, 1 being \"All\"",
+    ),
+    (
+        "cycle-layout",
+        UIAction::CycleLayout,
+        "cycle through layouts",
+    ),
+    (
+        "toggle-merged",
+        UIAction::ToggleMerged,
+        "interleave every source in the \"All\" tab",
+    ),
+    ("focus-next", UIAction::FocusNext, "focus the next pane"),
+    ("focus-prev", UIAction::FocusPrev, "focus the previous pane"),
+    ("restart", UIAction::Restart, "restart the focused command"),
+    ("kill", UIAction::Kill, "kill the focused command"),
+    ("scroll-up", UIAction::ScrollUp, "scroll up a line"),
+    ("scroll-down", UIAction::ScrollDown, "scroll down a line"),
+    ("page-up", UIAction::PageUp, "scroll up a page"),
+    ("page-down", UIAction::PageDown, "scroll down a page"),
+    (
+        "scroll-top",
+        UIAction::ScrollTop,
+        "scroll to the oldest line",
+    ),
+    ("follow", UIAction::Follow, "follow new lines"),
+    ("toggle-pause", UIAction::TogglePause, "pause or follow"),
+    ("search", UIAction::Search, "search"),
+    (
+        "next-match",
+        UIAction::NextMatch,
+        "go to the previous (older) match",
+    ),
+    (
+        "prev-match",
+        UIAction::PrevMatch,
+        "go to the next (newer) match",
+    ),
+    ("clear-search", UIAction::ClearSearch, "clear the search"),
+    ("clear", UIAction::Clear, "forget the lines read so far"),
+    (
+        "filter",
+        UIAction::Filter,
+        "only show lines matching a regex",
+    ),
+    ("exclude", UIAction::Exclude, "hide lines matching a regex"),
+    (
+        "toggle-filters",
+        UIAction::ToggleFilters,
+        "switch filters off and on",
+    ),
+    ("pop-filter", UIAction::PopFilter, "remove the last filter"),
+    (
+        "toggle-raw",
+        UIAction::ToggleRaw,
+        "show structured lines as read or rendered",
+    ),
+    (
+        "filter-app",
+        UIAction::FilterApp,
+        "only show lines of the app of the selected one",
+    ),
+    (
+        "exclude-app",
+        UIAction::ExcludeApp,
+        "hide lines of the app of the selected one",
+    ),
+    ("fold", UIAction::Fold, "fold or unfold the selected record"),
+    (
+        "fold-all",
+        UIAction::FoldAll,
+        "fold or unfold every long record",
+    ),
+    ("select", UIAction::Select, "start or stop selecting lines"),
+    (
+        "detail",
+        UIAction::ToggleDetail,
+        "show or hide the selected line in full",
+    ),
+    ("detail-up", UIAction::DetailUp, "scroll the details up"),
+    (
+        "detail-down",
+        UIAction::DetailDown,
+        "scroll the details down",
+    ),
+    ("command", UIAction::Command, "run a command"),
+    ("help", UIAction::Help, "show or hide this help"),
+    ("erase", UIAction::PromptErase, "erase a character"),
+    (
+        "toggle-regex",
+        UIAction::PromptToggleRegex,
+        "switch between text and regex",
+    ),
+    (
+        "complete",
+        UIAction::PromptComplete,
+        "complete commands and paths",
+    ),
+    ("submit", UIAction::PromptSubmit, "submit"),
+    ("cancel", UIAction::PromptCancel, "cancel"),
+];
+
+const DEFAULTS: &[(Mode, &str, &[&str])] = &[
+    (Mode::Normal, "quit", &["q", "<C-c>"]),
+    (Mode::Normal, "cycle-layout", &["l"]),
+    (Mode::Normal, "toggle-merged", &["m"]),
+    (Mode::Normal, "focus-next", &["<tab>"]),
+    (Mode::Normal, "focus-prev", &["<backtab>"]),
+    (Mode::Normal, "restart", &["r"]),
+    (Mode::Normal, "kill", &["K"]),
+    (Mode::Normal, "scroll-up", &["<up>", "k"]),
+    (Mode::Normal, "scroll-down", &["<down>", "j"]),
+    (Mode::Normal, "page-up", &["<pageup>", "<C-b>"]),
+    (Mode::Normal, "page-down", &["<pagedown>", "<C-f>"]),
+    (Mode::Normal, "scroll-top", &["<home>", "gg"]),
+    (Mode::Normal, "follow", &["<end>", "G"]),
+    (Mode::Normal, "toggle-pause", &["p"]),
+    (Mode::Normal, "search", &["/"]),
+    (Mode::Normal, "next-match", &["n"]),
+    (Mode::Normal, "prev-match", &["N"]),
+    (Mode::Normal, "clear-search", &["<esc>"]),
+    (Mode::Normal, "clear", &["<C-l>"]),
+    (Mode::Normal, "filter", &["f"]),
+    (Mode::Normal, "exclude", &["F"]),
+    (Mode::Normal, "toggle-filters", &["t"]),
+    (Mode::Normal, "pop-filter", &["T"]),
+    (Mode::Normal, "toggle-raw", &["R"]),
+    (Mode::Normal, "fold-all", &["z"]),
+    (Mode::Normal, "select", &["v", "<enter>"]),
+    (Mode::Normal, "command", &[":"]),
+    (Mode::Normal, "help", &["?"]),
+    (Mode::Search, "submit", &["<enter>"]),
+    (Mode::Search, "cancel", &["<esc>", "<C-c>"]),
+    (Mode::Search, "erase", &["<backspace>"]),
+    (Mode::Search, "toggle-regex", &["<C-r>"]),
+    (Mode::Command, "submit", &["<enter>"]),
+    (Mode::Command, "cancel", &["<esc>", "<C-c>"]),
+    (Mode::Command, "erase", &["<backspace>"]),
+    (Mode::Command, "complete", &["<tab>"]),
+    (Mode::Select, "select", &["v", "q", "<esc>"]),
+    (Mode::Select, "detail", &["<enter>"]),
+    (Mode::Select, "filter-app", &["a"]),
+    (Mode::Select, "exclude-app", &["A"]),
+    (Mode::Select, "fold", &["z", "<space>"]),
+    (Mode::Select, "fold-all", &["Z"]),
+    (Mode::Select, "scroll-up", &["<up>", "k"]),
+    (Mode::Select, "scroll-down", &["<down>", "j"]),
+    (Mode::Select, "page-up", &["<pageup>", "<C-b>"]),
+    (Mode::Select, "page-down", &["<pagedown>", "<C-f>"]),
+    (Mode::Select, "scroll-top", &["<home>", "gg"]),
+    (Mode::Select, "follow", &["<end>", "G"]),
+    (Mode::Select, "detail-up", &["K", "<S-up>"]),
+    (Mode::Select, "detail-down", &["J", "<S-down>"]),
+    (Mode::Help, "help", &["?", "q", "<esc>"]),
+    (Mode::Help, "scroll-up", &["<up>", "k"]),
+    (Mode::Help, "scroll-down", &["<down>", "j"]),
+    (Mode::Help, "page-up", &["<pageup>"]),
+    (Mode::Help, "page-down", &["<pagedown>"]),
+];
+
+impl UIAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        actions().find(|(n, ..)| n == name).map(|(_, a, _)| a)
+    }
+
+    /// Names of every action, which can also be run as commands
+    pub fn names() -> impl Iterator<Item = String> {
+        actions().map(|(name, ..)| name)
+    }
+}
+
+/// Every action, by name, along with what it does, with switching tabs expanded to each tab
+fn actions() -> impl Iterator<Item = (String, UIAction, &'static str)> {
+    ACTIONS
+        .iter()
+        .flat_map(|&(name, action, description)| match action {
+            UIAction::SwitchTabs(_) => (1..=TABS)
+                .map(|n| {
+                    (
+                        format!("{}-{}", name, n),
+                        UIAction::SwitchTabs(n),
+                        description,
+                    )
+                })
+                .collect(),
+            _ => vec![(name.to_string(), action, description)],
+        })
+}
+
+/// A key press, as written in bindings: `q`, `G`, `<C-r>`, `<pagedown>`...
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+];
+
+impl Key {
+    /// Parses a sequence of keys, like `gg` or `<C-w>j`
+    pub fn parse_sequence(spec: &str) -> anyhow::Result<Vec<Key>> {
+        let mut keys = vec![];
+        let mut rest = spec;
+
+        while let Some(c) = rest.chars().next() {
+            let (key, len) = match rest.find('>') {
+                Some(end) if c == '<' && end > 1 => (Self::parse_named(&rest[1..end])?, end + 1),
+                _ => (Self::from(KeyCode::Char(c)), c.len_utf8()),
+            };
+            keys.push(key);
+            rest = &rest[len..];
+        }
+
+        if keys.is_empty() {
+            bail!("empty key sequence");
+        }
+        Ok(keys)
+    }
+
+    /// `C-r`, `A-x`, `S-tab`, `f5`, `pageup`...
+    fn parse_named(name: &str) -> anyhow::Result<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = name;
+        while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "c" | "ctrl" => KeyModifiers::CONTROL,
+                "a" | "alt" | "m" => KeyModifiers::ALT,
+                "s" | "shift" => KeyModifiers::SHIFT,
+                _ => bail!("unknown modifier {:?} in <{}>", prefix, name),
+            };
+            rest = key;
+        }
+
+        let lower = rest.to_lowercase();
+        let code = match NAMED_KEYS.iter().find(|(n, _)| *n == lower) {
+            Some((_, code)) => *code,
+            None if rest.chars().count() == 1 => KeyCode::Char(rest.chars().next().unwrap()),
+            None => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n) => KeyCode::F(n),
+                None => return Err(anyhow!("unknown key <{}>", name)),
+            },
+        };
+
+        Ok(Key::from(KeyEvent::new(code, modifiers)))
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(code: KeyCode) -> Self {
+        Key::from(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & !KeyModifiers::SHIFT;
+        let code = match event.code {
+            // shift is already part of the character, and of back tab
+            KeyCode::Char(_) | KeyCode::BackTab => event.code,
+            KeyCode::Tab if event.modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            code => {
+                modifiers = event.modifiers;
+                code
+            }
+        };
+        Self { code, modifiers }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let named = NAMED_KEYS.iter().find(|(_, code)| *code == self.code);
+        let name = match (self.code, named) {
+            (_, Some((name, _))) => name.to_string(),
+            (KeyCode::Char(c), None) if self.modifiers.is_empty() => return write!(f, "{}", c),
+            (KeyCode::Char(c), None) => c.to_string(),
+            (KeyCode::F(n), None) => format!("f{}", n),
+            (code, None) => format!("{:?}", code).to_lowercase(),
+        };
+
+        write!(f, "<")?;
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}", prefix)?;
+            }
+        }
+        write!(f, "{}>", name)
+    }
+}
+
+pub struct Binding {
+    pub mode: Mode,
+    pub keys: Vec<Key>,
+    pub action: UIAction,
+}
+
+/// Turns key presses into actions, depending on the mode
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    /// keys pressed so far of a sequence like `gg`
+    pending: Vec<Key>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: vec![],
+            pending: vec![],
+        };
+        for (mode, action, keys) in DEFAULTS {
+            let keys: Vec<_> = keys.iter().map(|k| k.to_string()).collect();
+            keymap.bind(*mode, action, &keys).unwrap();
+        }
+        // each tab is switched to by its number
+        for n in 1..=TABS {
+            let action = format!("tab-{}", n);
+            keymap
+                .bind(Mode::Normal, &action, &[n.to_string()])
+                .unwrap();
+        }
+        keymap
+    }
+}
+
+/// A line of the help: what an action does, and the keys bound to it
+#[derive(Debug, PartialEq, Eq)]
+pub struct Help {
+    pub keys: Vec<String>,
+    pub description: &'static str,
+}
+
+impl Keymap {
+    /// Replaces the keys bound to an action. Keys bound to other actions in the same mode are taken
+    /// over
+    pub fn bind(&mut self, mode: Mode, action: &str, keys: &[String]) -> anyhow::Result<()> {
+        let action =
+            UIAction::from_name(action).ok_or_else(|| anyhow!("unknown action {:?}", action))?;
+        let sequences = keys
+            .iter()
+            .map(|k| Key::parse_sequence(k))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.bindings
+            .retain(|b| b.mode != mode || (b.action != action && !sequences.contains(&b.keys)));
+        for keys in sequences {
+            self.bindings.push(Binding { mode, keys, action });
+        }
+        Ok(())
+    }
+
+    /// Bound actions of each mode, in the order they're declared. Actions doing the same, like
+    /// switching to each tab, share a line
+    pub fn help(&self) -> Vec<(Mode, Vec<Help>)> {
+        Mode::ALL
+            .into_iter()
+            .map(|mode| {
+                let mut lines: Vec<Help> = vec![];
+                for (_, action, description) in actions() {
+                    let keys = self
+                        .bindings
+                        .iter()
+                        .filter(|b| b.mode == mode && b.action == action)
+                        .map(|b| b.keys.iter().map(Key::to_string).collect());
+
+                    match lines.iter_mut().find(|l| l.description == description) {
+                        Some(line) => line.keys.extend(keys),
+                        None => lines.push(Help {
+                            keys: keys.collect(),
+                            description,
+                        }),
+                    }
+                }
+                lines.retain(|l| !l.keys.is_empty());
+                (mode, lines)
+            })
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect()
+    }
+
+    /// Keys typed so far of an unfinished sequence
+    pub fn pending(&self) -> &[Key] {
+        &self.pending
+    }
+
+    /// Action for a key press. Keys that start a sequence wait for the rest of it, and characters
+    /// that aren't bound are typed when in a prompt
+    pub fn press(&mut self, mode: Mode, key: Key) -> UIAction {
+        self.pending.push(key);
+
+        let mut started = false;
+        for binding in self.bindings.iter().filter(|b| b.mode == mode) {
+            if binding.keys == self.pending {
+                self.pending.clear();
+                return binding.action;
+            }
+            started |= binding.keys.starts_with(&self.pending);
+        }
+        if started {
+            return UIAction::Noop;
+        }
+
+        // a broken sequence starts over from its last key
+        if self.pending.len() > 1 {
+            self.pending.clear();
+            return self.press(mode, key);
+        }
+        self.pending.clear();
+
+        match (mode, key.code) {
+            (Mode::Search | Mode::Command, KeyCode::Char(c))
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                UIAction::PromptType(c)
+            }
+            _ => UIAction::Noop,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Help, Key, Keymap, Mode, UIAction};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn press(keymap: &mut Keymap, mode: Mode, keys: &str) -> Vec<UIAction> {
+        Key::parse_sequence(keys)
+            .unwrap()
+            .into_iter()
+            .map(|key| keymap.press(mode, key))
+            .collect()
+    }
+
+    #[test]
+    fn parses_and_prints_keys() {
+        for spec in [
+            "q",
+            "G",
+            "<C-r>",
+            "<pagedown>",
+            "<A-S-f5>",
+            "<lt>",
+            "<space>",
+        ] {
+            let keys = Key::parse_sequence(spec).unwrap();
+            assert_eq!(keys.len(), 1);
+            assert_eq!(keys[0].to_string(), spec);
+        }
+
+        assert_eq!(Key::parse_sequence("gg").unwrap().len(), 2);
+        assert_eq!(Key::parse_sequence("<C-w>j").unwrap().len(), 2);
+        assert_eq!(Key::parse_sequence("<").unwrap()[0].to_string(), "<lt>");
+        assert!(Key::parse_sequence("<nope>").is_err());
+        assert!(Key::parse_sequence("").is_err());
+    }
+
+    #[test]
+    fn normalizes_shift() {
+        let shifted = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(Key::from(shifted).to_string(), "G");
+
+        let back_tab = KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(Key::from(back_tab).to_string(), "<backtab>");
+    }
+
+    #[test]
+    fn waits_for_sequences() {
+        let mut keymap = Keymap::default();
+
+        assert_eq!(
+            press(&mut keymap, Mode::Normal, "gg"),
+            [UIAction::Noop, UIAction::ScrollTop]
+        );
+        // broken sequences start over
+        assert_eq!(
+            press(&mut keymap, Mode::Normal, "gq"),
+            [UIAction::Noop, UIAction::Quit]
+        );
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn types_in_prompts() {
+        let mut keymap = Keymap::default();
+
+        assert_eq!(
+            press(&mut keymap, Mode::Search, "q<C-r><enter>"),
+            [
+                UIAction::PromptType('q'),
+                UIAction::PromptToggleRegex,
+                UIAction::PromptSubmit
+            ]
+        );
+    }
+
+    #[test]
+    fn generates_help_from_bindings() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Mode::Normal, "quit", &["Q".into()]).unwrap();
+        keymap.bind(Mode::Normal, "tab-9", &[]).unwrap();
+
+        let help = keymap.help();
+        let modes: Vec<_> = help.iter().map(|(mode, _)| *mode).collect();
+        assert_eq!(modes, Mode::ALL);
+
+        let (_, normal) = &help[0];
+        assert_eq!(
+            normal[0],
+            Help {
+                keys: vec!["Q".into()],
+                description: "quit"
+            }
+        );
+        assert_eq!(normal[1].keys, ["1", "2", "3", "4", "5", "6", "7", "8"]);
+        assert_eq!(normal[2].keys, ["l"]);
+    }
+
+    #[test]
+    fn rebinds_actions() {
+        let mut keymap = Keymap::default();
+        keymap
+            .bind(Mode::Normal, "scroll-down", &["j".into(), "<down>".into()])
+            .unwrap();
+        keymap.bind(Mode::Normal, "quit", &["k".into()]).unwrap();
+
+        // "k" is taken over from scrolling up
+        assert_eq!(
+            press(&mut keymap, Mode::Normal, "jkq"),
+            [UIAction::ScrollDown, UIAction::Quit, UIAction::Noop]
+        );
+        assert!(keymap.bind(Mode::Normal, "fly", &["x".into()]).is_err());
+    }
+
+    #[test]
+    fn scrolls_with_the_same_keys_in_every_mode() {
+        let mut keymap = Keymap::default();
+
+        for mode in [Mode::Normal, Mode::Select, Mode::Help] {
+            assert_eq!(
+                press(&mut keymap, mode, "kj"),
+                [UIAction::ScrollUp, UIAction::ScrollDown]
+            );
+        }
+        assert_eq!(press(&mut keymap, Mode::Normal, "K"), [UIAction::Kill]);
+    }
+}
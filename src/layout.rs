@@ -0,0 +1,191 @@
+use tui::layout::Rect;
+
+// smallest pane that still fits a border plus a few characters of content
+const MIN_WIDTH: u16 = 12;
+const MIN_HEIGHT: u16 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Columns,
+    Rows,
+    Grid,
+    MainStack,
+}
+
+impl Mode {
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Columns => Mode::Rows,
+            Mode::Rows => Mode::Grid,
+            Mode::Grid => Mode::MainStack,
+            Mode::MainStack => Mode::Columns,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Columns => "columns",
+            Mode::Rows => "rows",
+            Mode::Grid => "grid",
+            Mode::MainStack => "main+stack",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "columns" => Some(Mode::Columns),
+            "rows" => Some(Mode::Rows),
+            "grid" => Some(Mode::Grid),
+            "main+stack" | "main-stack" => Some(Mode::MainStack),
+            _ => None,
+        }
+    }
+}
+
+/// Tiles `n` panes into `area`, returning which pane goes into which rect.
+/// When the area is too small to fit every pane, only as many as fit are
+/// returned, always including the focused one.
+pub fn arrange(area: Rect, n: usize, focus: usize, mode: Mode) -> Vec<(usize, Rect)> {
+    if n == 0 {
+        return vec![];
+    }
+    let focus = focus.min(n - 1);
+
+    let rects = tile(area, n, mode);
+
+    let order: Vec<usize> = match mode {
+        // the focused pane is always the main one, followed by the others in order
+        Mode::MainStack => std::iter::once(focus)
+            .chain((0..n).filter(|&i| i != focus))
+            .collect(),
+        // slide a window over the panes so that the focused one is visible
+        _ => {
+            let start = (focus + 1).saturating_sub(rects.len());
+            (start..n).collect()
+        }
+    };
+
+    order.into_iter().zip(rects).collect()
+}
+
+fn tile(area: Rect, n: usize, mode: Mode) -> Vec<Rect> {
+    let max_cols = (area.width / MIN_WIDTH).max(1) as usize;
+    let max_rows = (area.height / MIN_HEIGHT).max(1) as usize;
+
+    match mode {
+        Mode::Columns if n <= max_cols => columns(area, n),
+        Mode::Rows if n <= max_rows => rows(area, n),
+        Mode::MainStack if n > 1 && max_cols > 1 => {
+            let main_width = area.width * 3 / 5;
+            let main = Rect {
+                width: main_width,
+                ..area
+            };
+            let stack = Rect {
+                x: area.x + main_width,
+                width: area.width - main_width,
+                ..area
+            };
+            std::iter::once(main)
+                .chain(rows(stack, (n - 1).min(max_rows)))
+                .collect()
+        }
+        _ => grid(area, n, max_cols, max_rows),
+    }
+}
+
+fn grid(area: Rect, n: usize, max_cols: usize, max_rows: usize) -> Vec<Rect> {
+    let n = n.min(max_cols * max_rows);
+    let cols = ((n as f64).sqrt().ceil() as usize).min(max_cols);
+    let rows_count = n.div_ceil(cols);
+
+    let mut rects = vec![];
+    for (i, row) in rows(area, rows_count).into_iter().enumerate() {
+        // the last row may be shorter, in which case its panes grow to fill it
+        let in_row = cols.min(n - i * cols);
+        rects.extend(columns(row, in_row));
+    }
+    rects
+}
+
+fn columns(area: Rect, n: usize) -> Vec<Rect> {
+    split(area.x, area.width, n)
+        .map(|(x, width)| Rect { x, width, ..area })
+        .collect()
+}
+
+fn rows(area: Rect, n: usize) -> Vec<Rect> {
+    split(area.y, area.height, n)
+        .map(|(y, height)| Rect { y, height, ..area })
+        .collect()
+}
+
+// splits a length into n near-equal segments, spreading the remainder over the first ones
+fn split(start: u16, len: u16, n: usize) -> impl Iterator<Item = (u16, u16)> {
+    let n = n.max(1) as u16;
+    let (base, extra) = (len / n, len % n);
+    (0..n).scan(start, move |pos, i| {
+        let size = base + u16::from(i < extra);
+        let segment = (*pos, size);
+        *pos += size;
+        Some(segment)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arrange, Mode};
+    use tui::layout::Rect;
+
+    fn area(width: u16, height: u16) -> Rect {
+        Rect {
+            x: 0,
+            y: 3,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn tiles_every_pane() {
+        for mode in [Mode::Columns, Mode::Rows, Mode::Grid, Mode::MainStack] {
+            for n in 1..8 {
+                let tiles = arrange(area(200, 60), n, 0, mode);
+                assert_eq!(tiles.len(), n, "{:?} with {} panes", mode, n);
+
+                let covered: u32 = tiles
+                    .iter()
+                    .map(|(_, r)| r.width as u32 * r.height as u32)
+                    .sum();
+                assert_eq!(covered, 200 * 60, "{:?} with {} panes", mode, n);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_shape() {
+        let tiles = arrange(area(90, 30), 5, 0, Mode::Grid);
+        let widths: Vec<_> = tiles.iter().map(|(_, r)| r.width).collect();
+        // 3 on the first row, 2 wider ones on the second
+        assert_eq!(widths, vec![30, 30, 30, 45, 45]);
+    }
+
+    #[test]
+    fn main_stack_puts_focus_first() {
+        let tiles = arrange(area(100, 30), 3, 2, Mode::MainStack);
+        let order: Vec<_> = tiles.iter().map(|(i, _)| *i).collect();
+        assert_eq!(order, vec![2, 0, 1]);
+        assert_eq!(tiles[0].1.width, 60);
+    }
+
+    #[test]
+    fn degrades_on_small_areas() {
+        let tiles = arrange(area(30, 5), 6, 4, Mode::Columns);
+        let order: Vec<_> = tiles.iter().map(|(i, _)| *i).collect();
+        assert_eq!(order, vec![3, 4]);
+
+        let tiles = arrange(area(5, 2), 6, 4, Mode::Grid);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].0, 4);
+    }
+}
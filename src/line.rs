@@ -0,0 +1,162 @@
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tui::text::Spans;
+
+use crate::{
+    circular::CircularBuffer,
+    format::{Parsed, Record},
+};
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// A line read from one of the sources
+pub struct Line {
+    /// position in the order lines arrived in, across every source
+    pub seq: u64,
+    /// time the line was logged at, when it or one of the lines before it start with a timestamp
+    pub time: Option<NaiveDateTime>,
+    pub spans: Spans<'static>,
+    /// the text of the spans, without styles
+    pub text: String,
+    /// fields of the line, for lines in a format we know
+    pub record: Option<Record>,
+    /// the line rendered from its record, and the text of that
+    pub pretty: Option<(Spans<'static>, String)>,
+    /// first line of the record the line is part of, which is itself unless it continues the
+    /// line before it
+    pub group: u64,
+}
+
+impl Line {
+    pub fn new(spans: Spans<'static>) -> Self {
+        let text = text(&spans);
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            seq,
+            time: leading_timestamp(&text),
+            spans,
+            text,
+            record: None,
+            pretty: None,
+            group: seq,
+        }
+    }
+}
+
+/// Adds a line to the history of a source, along with what its parser made of it. Lines without a
+/// timestamp of their own, like the ones in a stack trace, take the one of the line before them
+pub fn push(history: &mut CircularBuffer<Line>, spans: Spans<'static>, parsed: Parsed) {
+    let Parsed {
+        record,
+        continued,
+        pretty,
+        time,
+    } = parsed;
+    let mut line = Line::new(spans);
+    if continued {
+        if let Some(previous) = history.rev_iter().next() {
+            line.group = previous.group;
+        }
+    }
+    if line.time.is_none() {
+        line.time = time.or_else(|| history.rev_iter().next().and_then(|l| l.time));
+    }
+    line.record = record;
+    line.pretty = pretty.map(|spans| {
+        let text = text(&spans);
+        (spans, text)
+    });
+    history.push(line);
+}
+
+/// The text of spans, without styles
+pub fn text(spans: &Spans) -> String {
+    spans.0.iter().map(|s| s.content.as_ref()).collect()
+}
+
+/// Parses ISO 8601-like timestamps (`2023-02-10T13:45:12.345Z`, `[2023-02-10 13:45:12,345]`, ...)
+/// at the start of a line, or the `Feb 10 13:45:12` ones of syslog and `10/Feb/2023:13:45:12` ones
+/// of access logs. Timezones are ignored, as lines are only ever compared to each other
+pub fn leading_timestamp(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim_start_matches(['[', ' ']);
+    if let Some(time) = syslog_timestamp(text) {
+        return Some(time);
+    }
+    if let Some(time) = text.get(..20) {
+        if let Ok(time) = NaiveDateTime::parse_from_str(time, "%d/%b/%Y:%H:%M:%S") {
+            return Some(time);
+        }
+    }
+    let head = text.get(..19)?;
+
+    let date = NaiveDate::parse_from_str(head.get(..10)?, "%Y-%m-%d").ok()?;
+    if !matches!(head.as_bytes()[10], b'T' | b' ') {
+        return None;
+    }
+    let time = NaiveTime::parse_from_str(head.get(11..)?, "%H:%M:%S").ok()?;
+
+    // fractional seconds, with either separator
+    let mut nanos = 0;
+    if let Some(fraction) = text[19..].strip_prefix(['.', ',']) {
+        let digits: String = fraction
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .take(9)
+            .collect();
+        if !digits.is_empty() {
+            nanos = format!("{:0<9}", digits).parse().ok()?;
+        }
+    }
+
+    Some(NaiveDateTime::new(date, time) + chrono::Duration::nanoseconds(nanos))
+}
+
+// syslog timestamps have no year, so they're taken to be from the current one
+fn syslog_timestamp(text: &str) -> Option<NaiveDateTime> {
+    let head = text.get(..15)?;
+    if !head.starts_with(char::is_alphabetic) {
+        return None;
+    }
+    let dated = format!("{} {}", Local::now().year(), head);
+    NaiveDateTime::parse_from_str(&dated, "%Y %b %e %H:%M:%S").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::leading_timestamp;
+    use chrono::{Datelike, Local, NaiveDate, Timelike};
+
+    #[test]
+    fn parses_leading_timestamps() {
+        let expected = NaiveDate::from_ymd_opt(2023, 2, 10)
+            .unwrap()
+            .and_hms_milli_opt(13, 45, 12, 345)
+            .unwrap();
+
+        assert_eq!(
+            leading_timestamp("2023-02-10T13:45:12.345Z INFO started"),
+            Some(expected)
+        );
+        assert_eq!(
+            leading_timestamp("[2023-02-10 13:45:12,345] WARN slow"),
+            Some(expected)
+        );
+        assert_eq!(
+            leading_timestamp("2023-02-10 13:45:12 done"),
+            Some(expected.with_nanosecond(0).unwrap())
+        );
+        assert_eq!(
+            leading_timestamp("Feb  1 13:45:12 web-1 sshd[4242]: started"),
+            NaiveDate::from_ymd_opt(Local::now().year(), 2, 1)
+                .unwrap()
+                .and_hms_opt(13, 45, 12)
+        );
+        assert_eq!(
+            leading_timestamp("[10/Feb/2023:13:45:12 +0000]"),
+            Some(expected.with_nanosecond(0).unwrap())
+        );
+        assert_eq!(leading_timestamp("GET /health 200"), None);
+        assert_eq!(leading_timestamp("2023-02-10 is not a timestamp"), None);
+    }
+}
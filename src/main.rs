@@ -1,14 +1,32 @@
 mod app;
 mod args;
 mod circular;
+mod command;
+mod config;
+mod filter;
+mod format;
+mod group;
+mod highlight;
+mod keymap;
+mod layout;
+mod line;
+mod merge;
+mod prompt;
+mod scope;
+mod search;
+mod spans;
+mod stats;
 // mod file_watcher;
+mod titles;
 mod ui;
+mod view;
 mod watchers;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = args::parse();
-    let app = app::App::new(args).await?;
+    let settings = config::Config::load()?.settings(args.profile.as_deref())?;
+    let app = app::App::new(args, settings).await?;
 
     ui::run(app).await?;
 
@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+
+use crate::line::Line;
+
+/// Interleaves the lines of several sources, newest first. Lines are ordered by their timestamps
+/// when both have one, and by arrival otherwise. Each source's own order is always kept
+pub struct MergedRev<I: Iterator> {
+    heads: Vec<std::iter::Peekable<I>>,
+}
+
+impl<'a, I> MergedRev<I>
+where
+    I: Iterator<Item = &'a Line>,
+{
+    /// Takes each source's lines, newest first
+    pub fn new(sources: impl IntoIterator<Item = I>) -> Self {
+        Self {
+            heads: sources.into_iter().map(Iterator::peekable).collect(),
+        }
+    }
+}
+
+impl<'a, I> Iterator for MergedRev<I>
+where
+    I: Iterator<Item = &'a Line>,
+{
+    /// index of the source, and the line
+    type Item = (usize, &'a Line);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut newest: Option<(usize, &'a Line)> = None;
+
+        for (i, head) in self.heads.iter_mut().enumerate() {
+            let Some(&line) = head.peek() else {
+                continue;
+            };
+            let is_newer = match newest {
+                None => true,
+                Some((_, other)) => compare(line, other) == Ordering::Greater,
+            };
+            if is_newer {
+                newest = Some((i, line));
+            }
+        }
+
+        let (i, line) = newest?;
+        self.heads[i].next();
+        Some((i, line))
+    }
+}
+
+fn compare(a: &Line, b: &Line) -> Ordering {
+    match (a.time, b.time) {
+        (Some(a_time), Some(b_time)) => a_time.cmp(&b_time).then(a.seq.cmp(&b.seq)),
+        _ => a.seq.cmp(&b.seq),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MergedRev;
+    use crate::{
+        circular::CircularBuffer,
+        format::Parsed,
+        line::{self, Line},
+    };
+    use tui::text::Spans;
+
+    fn push(history: &mut CircularBuffer<Line>, texts: &[&str]) {
+        for text in texts {
+            line::push(history, Spans::from(text.to_string()), Parsed::default());
+        }
+    }
+
+    // each line as "source:last word"
+    fn merge(sources: &[CircularBuffer<Line>]) -> Vec<String> {
+        MergedRev::new(sources.iter().map(|s| s.rev_iter()))
+            .map(|(i, l)| {
+                let last = l
+                    .spans
+                    .0
+                    .last()
+                    .unwrap()
+                    .content
+                    .rsplit(' ')
+                    .next()
+                    .unwrap();
+                format!("{}:{}", i, last)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn interleaves_by_arrival() {
+        let mut sources = [CircularBuffer::new(10), CircularBuffer::new(10)];
+        push(&mut sources[0], &["a1"]);
+        push(&mut sources[1], &["b1"]);
+        push(&mut sources[0], &["a2"]);
+
+        assert_eq!(merge(&sources), ["0:a2", "1:b1", "0:a1"]);
+    }
+
+    #[test]
+    fn interleaves_by_timestamp() {
+        let mut sources = [CircularBuffer::new(10), CircularBuffer::new(10)];
+        push(
+            &mut sources[0],
+            &[
+                "2023-01-01 10:00:03 a1",
+                "    at continuation",
+                "2023-01-01 10:00:04 a2",
+            ],
+        );
+        push(
+            &mut sources[1],
+            &[
+                "2023-01-01 10:00:01 b1",
+                "2023-01-01 10:00:03.5 b2",
+                "2023-01-01 10:00:05 b3",
+            ],
+        );
+
+        assert_eq!(
+            merge(&sources),
+            ["1:b3", "0:a2", "1:b2", "0:continuation", "0:a1", "1:b1"]
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use crate::search::Search;
+
+/// What is being typed at the bottom of the screen
+pub struct Prompt {
+    pub kind: Kind,
+    pub query: String,
+    pub regex: bool,
+    /// whether the query is a regex that doesn't compile
+    pub invalid: bool,
+    /// what the last completion could have been
+    pub candidates: Vec<String>,
+}
+
+pub enum Kind {
+    Search {
+        /// search to go back to when the prompt is cancelled
+        previous: Option<Search>,
+    },
+    Filter {
+        exclude: bool,
+        /// whether the filter being typed was added to the pane already
+        applied: bool,
+    },
+    Command,
+}
+
+impl Prompt {
+    pub fn new(kind: Kind, regex: bool) -> Self {
+        Self {
+            kind,
+            query: String::new(),
+            regex,
+            invalid: false,
+            candidates: vec![],
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            Kind::Search { .. } => "/",
+            Kind::Filter { exclude: false, .. } => "filter: ",
+            Kind::Filter { exclude: true, .. } => "exclude: ",
+            Kind::Command => ":",
+        }
+    }
+}
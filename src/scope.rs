@@ -0,0 +1,53 @@
+use anyhow::Context;
+use glob::Pattern;
+use serde::Deserialize;
+
+/// Files a rule applies to, matched either by path or by file name. Rules without a pattern
+/// apply to every source, including the ones that aren't files
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FileScope(Option<Pattern>);
+
+impl FileScope {
+    pub fn new(files: &str) -> anyhow::Result<Self> {
+        let pattern =
+            Pattern::new(files).with_context(|| format!("invalid file pattern {:?}", files))?;
+        Ok(Self(Some(pattern)))
+    }
+
+    /// Whether the source at the given path is in scope
+    pub fn includes(&self, path: Option<&str>) -> bool {
+        match (&self.0, path) {
+            (None, _) => true,
+            (Some(files), Some(path)) => {
+                files.matches(path) || path.rsplit('/').next().is_some_and(|f| files.matches(f))
+            }
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl TryFrom<String> for FileScope {
+    type Error = anyhow::Error;
+
+    fn try_from(files: String) -> anyhow::Result<Self> {
+        Self::new(&files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileScope;
+
+    #[test]
+    fn scopes_rules_to_files() {
+        let files = FileScope::new("access*.log").unwrap();
+        assert!(files.includes(Some("/var/log/nginx/access.log")));
+        assert!(files.includes(Some("access.log")));
+        assert!(!files.includes(Some("/var/log/nginx/error.log")));
+        assert!(!files.includes(None));
+
+        assert!(FileScope::default().includes(None));
+        assert!(FileScope::new("[a").is_err());
+    }
+}
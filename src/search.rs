@@ -0,0 +1,218 @@
+use std::{collections::HashMap, ops::Range};
+
+use regex::{Regex, RegexBuilder};
+
+/// A search over the lines of a pane, in plain text or as a regex
+pub struct Search {
+    pub query: String,
+    pub regex: bool,
+    pattern: Regex,
+    /// every match as of the last update, in the order of the pane, oldest first. Lines of merged
+    /// panes are in the order of their time rather than of their sequence numbers
+    matches: Vec<Match>,
+    /// indexes of the matches of each line
+    lines: HashMap<u64, Range<usize>>,
+    current: Option<Match>,
+    jump: Option<Jump>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Match {
+    seq: u64,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Jump {
+    Newest,
+    Older,
+    Newer,
+}
+
+impl Search {
+    pub fn new(query: &str, regex: bool) -> Result<Self, regex::Error> {
+        Ok(Self {
+            query: query.to_string(),
+            regex,
+            pattern: pattern(query, regex)?,
+            matches: vec![],
+            lines: HashMap::new(),
+            current: None,
+            jump: Some(Jump::Newest),
+        })
+    }
+
+    /// Moves to the match before the current one, wrapping around to the newest
+    pub fn older(&mut self) {
+        self.jump = Some(Jump::Older);
+    }
+
+    /// Moves to the match after the current one, wrapping around to the oldest
+    pub fn newer(&mut self) {
+        self.jump = Some(Jump::Newer);
+    }
+
+    /// Finds the matches in `lines`, given newest first. Returns the line holding the new current
+    /// match if `older` or `newer` were called since the last update
+    pub fn update<'a>(&mut self, lines: impl Iterator<Item = (u64, &'a str)>) -> Option<u64> {
+        let lines: Vec<_> = lines.collect();
+        self.matches.clear();
+        self.lines.clear();
+        for (seq, text) in lines.into_iter().rev() {
+            let start = self.matches.len();
+            self.matches
+                .extend(self.pattern.find_iter(text).map(|m| Match {
+                    seq,
+                    start: m.start(),
+                    end: m.end(),
+                }));
+            if self.matches.len() > start {
+                self.lines.insert(seq, start..self.matches.len());
+            }
+        }
+
+        let jump = self.jump.take()?;
+        let last = self.matches.len().checked_sub(1);
+        // a current match that's gone, like one dropped from the history, counts as the oldest
+        let index = match (jump, self.position(), last) {
+            (_, _, None) => None,
+            (Jump::Older, Some(i), Some(last)) => Some(i.checked_sub(1).unwrap_or(last)),
+            (Jump::Newer, Some(i), Some(last)) => Some(if i < last { i + 1 } else { 0 }),
+            (Jump::Newer, None, Some(_)) if self.current.is_some() => Some(0),
+            (_, _, last) => last,
+        };
+        self.current = index.map(|i| self.matches[i]);
+
+        self.current.map(|m| m.seq)
+    }
+
+    /// Ranges matched in the given line, and whether each is the current match
+    pub fn ranges(&self, seq: u64) -> Vec<(Range<usize>, bool)> {
+        let matches = match self.lines.get(&seq) {
+            Some(range) => &self.matches[range.clone()],
+            None => &[],
+        };
+        matches
+            .iter()
+            .map(|m| (m.start..m.end, Some(*m) == self.current))
+            .collect()
+    }
+
+    pub fn status(&self) -> String {
+        match (self.position(), self.matches.len()) {
+            (_, 0) => "no matches".into(),
+            (Some(i), k) => format!("match {} of {}", i + 1, k),
+            (None, 1) => "1 match".into(),
+            (None, k) => format!("{} matches", k),
+        }
+    }
+
+    /// index of the current match, if it's still around
+    fn position(&self) -> Option<usize> {
+        let current = self.current?;
+        let range = self.lines.get(&current.seq)?;
+        self.matches[range.clone()]
+            .iter()
+            .position(|m| *m == current)
+            .map(|i| range.start + i)
+    }
+}
+
+/// Compiles what was typed in the prompt. Queries without uppercase letters ignore case
+pub fn pattern(query: &str, regex: bool) -> Result<Regex, regex::Error> {
+    let source = if regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+
+    RegexBuilder::new(&source)
+        .case_insensitive(!query.chars().any(char::is_uppercase))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Search;
+
+    const LINES: [&str; 4] = ["Error: a", "ok", "error: b, error: c", "ok"];
+
+    fn update(search: &mut Search) -> Option<u64> {
+        search.update(LINES.iter().enumerate().rev().map(|(i, l)| (i as u64, *l)))
+    }
+
+    #[test]
+    fn ignores_case_unless_the_query_has_uppercase() {
+        let mut search = Search::new("error", false).unwrap();
+        update(&mut search);
+        assert_eq!(search.status(), "match 3 of 3");
+
+        let mut search = Search::new("Error", false).unwrap();
+        update(&mut search);
+        assert_eq!(search.status(), "match 1 of 1");
+    }
+
+    #[test]
+    fn escapes_plain_queries() {
+        let mut search = Search::new("b,", false).unwrap();
+        update(&mut search);
+        assert_eq!(search.ranges(2), vec![(7..9, true)]);
+
+        let mut search = Search::new("[bc]$", true).unwrap();
+        update(&mut search);
+        assert_eq!(search.ranges(2), vec![(17..18, true)]);
+        assert!(Search::new("[bc]$", false).is_ok());
+        assert!(Search::new("[bc", true).is_err());
+    }
+
+    #[test]
+    fn jumps_between_matches() {
+        let mut search = Search::new("error", false).unwrap();
+        assert_eq!(update(&mut search), Some(2));
+        assert_eq!(search.ranges(2), vec![(0..5, false), (10..15, true)]);
+
+        search.older();
+        assert_eq!(update(&mut search), Some(2));
+        assert_eq!(search.status(), "match 2 of 3");
+        search.older();
+        assert_eq!(update(&mut search), Some(0));
+
+        // wraps around
+        search.older();
+        assert_eq!(update(&mut search), Some(2));
+        assert_eq!(search.status(), "match 3 of 3");
+        search.newer();
+        assert_eq!(update(&mut search), Some(0));
+
+        // only jumps when asked to
+        assert_eq!(update(&mut search), None);
+        assert_eq!(search.status(), "match 1 of 3");
+    }
+
+    #[test]
+    fn follows_the_order_of_merged_panes() {
+        // lines from two sources, ordered by their time rather than by when they were read
+        let lines = [(3, "error: a"), (0, "error: b"), (2, "ok"), (1, "error: c")];
+        let mut search = Search::new("error", false).unwrap();
+        let update = |search: &mut Search| search.update(lines.iter().rev().copied());
+
+        assert_eq!(update(&mut search), Some(1));
+        assert_eq!(search.status(), "match 3 of 3");
+        assert_eq!(search.ranges(1), vec![(0..5, true)]);
+        assert_eq!(search.ranges(3), vec![(0..5, false)]);
+
+        search.older();
+        assert_eq!(update(&mut search), Some(0));
+        assert_eq!(search.status(), "match 2 of 3");
+        search.older();
+        assert_eq!(update(&mut search), Some(3));
+        assert_eq!(search.status(), "match 1 of 3");
+        search.newer();
+        assert_eq!(update(&mut search), Some(0));
+        search.newer();
+        assert_eq!(update(&mut search), Some(1));
+        search.newer();
+        assert_eq!(update(&mut search), Some(3));
+    }
+}
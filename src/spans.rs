@@ -0,0 +1,102 @@
+use std::ops::Range;
+
+use tui::{
+    style::Style,
+    text::{Span, Spans},
+};
+
+/// Applies styles over byte ranges of the text of `spans`, on top of the styles they already have.
+/// Later ranges take precedence where they overlap
+pub fn patch(spans: &Spans<'static>, ranges: &[(Range<usize>, Style)]) -> Spans<'static> {
+    if ranges.is_empty() {
+        return spans.clone();
+    }
+
+    let mut patched = vec![];
+    let mut offset = 0;
+
+    for span in &spans.0 {
+        let content = span.content.as_ref();
+        let end = offset + content.len();
+
+        // places where the style changes within this span
+        let mut cuts: Vec<_> = ranges
+            .iter()
+            .flat_map(|(r, _)| [r.start, r.end])
+            .filter(|cut| (offset..end).contains(cut))
+            .chain([offset, end])
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for piece in cuts.windows(2) {
+            let (from, to) = (piece[0], piece[1]);
+            let style = ranges
+                .iter()
+                .filter(|(r, _)| r.start <= from && to <= r.end)
+                .fold(span.style, |style, (_, s)| style.patch(*s));
+            patched.push(Span::styled(
+                content[from - offset..to - offset].to_string(),
+                style,
+            ));
+        }
+
+        offset = end;
+    }
+
+    Spans::from(patched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::patch;
+    use tui::{
+        style::{Color, Style},
+        text::{Span, Spans},
+    };
+
+    fn pieces(spans: &Spans) -> Vec<(String, Style)> {
+        spans
+            .0
+            .iter()
+            .map(|s| (s.content.to_string(), s.style))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_existing_styles() {
+        let red = Style::default().fg(Color::Red);
+        let bg = Style::default().bg(Color::Yellow);
+        let spans = Spans::from(vec![Span::styled("ERROR", red), Span::raw(" disk full")]);
+
+        let patched = patch(&spans, &[(3..7, bg)]);
+
+        assert_eq!(
+            pieces(&patched),
+            vec![
+                ("ERR".into(), red),
+                ("OR".into(), red.bg(Color::Yellow)),
+                (" d".into(), bg),
+                ("isk full".into(), Style::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_ranges_win() {
+        let spans = Spans::from("abcdef");
+        let blue = Style::default().fg(Color::Blue);
+        let green = Style::default().fg(Color::Green);
+
+        let patched = patch(&spans, &[(0..6, blue), (2..4, green)]);
+
+        assert_eq!(
+            pieces(&patched),
+            vec![
+                ("ab".into(), blue),
+                ("cd".into(), green),
+                ("ef".into(), blue),
+            ]
+        );
+    }
+}
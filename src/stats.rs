@@ -0,0 +1,94 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// how far back the rate of new lines is measured
+const WINDOW: Duration = Duration::from_secs(5);
+/// samples closer together than this are merged, so bursts of renders don't pile them up
+const RESOLUTION: Duration = Duration::from_millis(100);
+
+/// Rate at which a source receives lines, from samples of how many it received in total
+#[derive(Default)]
+pub struct Rate {
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl Rate {
+    pub fn sample(&mut self, total: usize, now: Instant) {
+        match self.samples.back_mut() {
+            Some((at, last)) if now.duration_since(*at) < RESOLUTION => *last = total,
+            _ => self.samples.push_back((now, total)),
+        }
+
+        // keep the one sample right before the window, which the window is measured from
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Lines per second over the last few seconds
+    pub fn per_sec(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((start, first)), Some((end, last))) if end > start => {
+                (last - first) as f64 / end.duration_since(*start).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Sizes like `512 B`, `1.5 KiB` or `12.0 MiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_size, Rate};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn measures_recent_lines() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut rate = Rate::default();
+        assert_eq!(rate.per_sec(), 0.0);
+
+        rate.sample(0, at(0));
+        rate.sample(10, at(1000));
+        rate.sample(20, at(2000));
+        assert_eq!(rate.per_sec(), 10.0);
+
+        // close samples are merged
+        rate.sample(25, at(2050));
+        assert_eq!(rate.per_sec(), 12.5);
+
+        // the source went quiet
+        rate.sample(25, at(7000));
+        assert_eq!(rate.per_sec(), 0.0);
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(12 * 1024 * 1024), "12.0 MiB");
+    }
+}
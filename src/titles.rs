@@ -0,0 +1,62 @@
+/// Builds a display name for each path: its basename, extended with parent
+/// components only as far as needed to tell apart paths sharing a basename.
+pub fn unique_suffixes(paths: &[&str]) -> Vec<String> {
+    let parts: Vec<Vec<&str>> = paths
+        .iter()
+        .map(|p| {
+            p.split('/')
+                .filter(|c| !c.is_empty() && *c != ".")
+                .collect()
+        })
+        .collect();
+
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, own)| {
+            let mut n = 1;
+            while n < own.len()
+                && parts
+                    .iter()
+                    .enumerate()
+                    .any(|(j, other)| j != i && other.ends_with(&own[own.len() - n..]))
+            {
+                n += 1;
+            }
+            own[own.len().saturating_sub(n)..].join("/")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unique_suffixes;
+
+    #[test]
+    fn basenames_when_distinct() {
+        assert_eq!(
+            unique_suffixes(&["/var/log/syslog", "./app.log", "logs/worker.log"]),
+            vec!["syslog", "app.log", "worker.log"]
+        );
+    }
+
+    #[test]
+    fn extends_shared_basenames() {
+        assert_eq!(
+            unique_suffixes(&["api/logs/out.log", "web/logs/out.log", "web/err.log"]),
+            vec!["api/logs/out.log", "web/logs/out.log", "err.log"]
+        );
+        assert_eq!(
+            unique_suffixes(&["/srv/a/out.log", "/srv/b/out.log"]),
+            vec!["a/out.log", "b/out.log"]
+        );
+    }
+
+    #[test]
+    fn nested_suffix() {
+        assert_eq!(
+            unique_suffixes(&["out.log", "a/out.log"]),
+            vec!["out.log", "a/out.log"]
+        );
+    }
+}
@@ -1,27 +1,46 @@
-use crate::{app::App, watchers::file::FileWatcher};
-use std::io;
+use crate::{
+    app::{App, Pane},
+    command::{Command, Setting},
+    config::Theme,
+    filter::Filter,
+    format::{json, Shown},
+    group,
+    highlight::{self, Rule},
+    keymap::{Key, Mode, UIAction},
+    layout,
+    line::Line,
+    merge::MergedRev,
+    search::Search,
+    spans, stats, titles,
+    view::{View, Window},
+    watchers::Watcher,
+};
+use std::{io, panic, time::Duration};
 
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEvent},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, EventStream, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use futures::StreamExt;
-use tokio::{select, sync::MutexGuard};
+use tokio::{select, sync::MutexGuard, time};
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Paragraph, Tabs, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Tabs, Wrap},
     Frame, Terminal,
 };
 
-enum UIAction {
-    SwitchTabs(usize),
-    Noop,
-    Quit,
-}
+// lines scrolled by each turn of the mouse wheel
+const MOUSE_SCROLL: usize = 3;
+// lines scrolled by paging through the help
+const HELP_PAGE: u16 = 10;
+// how often the screen is redrawn even when nothing happens
+const TICK: Duration = Duration::from_secs(1);
 
 pub async fn run(mut app: App) -> anyhow::Result<()> {
     // setup
@@ -31,9 +50,17 @@ pub async fn run(mut app: App) -> anyhow::Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    run_app(&mut terminal, &mut app).await?;
+    // a panic must not leave the terminal in raw mode either
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+        hook(info);
+    }));
+
+    let res = run_app(&mut terminal, &mut app).await;
 
-    // teardown
+    // teardown, whether or not the app failed
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -42,52 +69,77 @@ pub async fn run(mut app: App) -> anyhow::Result<()> {
     )?;
     terminal.show_cursor()?;
 
-    Ok(())
+    res
 }
 
 async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> anyhow::Result<()> {
     let mut term_events = EventStream::new();
+    let mut ticks = time::interval(TICK);
 
     'mainloop: loop {
         // render
         {
             // terminal.draw is not async, so we need to grab MutexGuards for all the histories
             // before
-            let tails = futures::future::join_all(app.watchers.iter().map(move |w| w.lock())).await;
+            let watchers: Vec<_> = app.panes.iter().map(|p| p.watcher.clone()).collect();
+            let tails = futures::future::join_all(watchers.iter().map(|w| w.lock())).await;
 
             terminal.draw(|f| ui(f, app, &tails))?;
+
+            let totals: Vec<_> = tails.iter().map(|t| t.history().total_elements()).collect();
+            app.mark_seen(&totals);
+            app.sample_rates(&totals);
         }
 
         // wait for events
         select! {
-            () = app.wait() =>{
+            () = app.wait() => {
                 /* update was triggered by one of the files. looping */
             }
+            // redraw now and then, so that stats stay current when nothing happens
+            _ = ticks.tick() => {}
             Some(maybe_event) = term_events.next() => {
-                match translate_event(maybe_event) {
-                    UIAction::SwitchTabs(n) => app.move_to_tab(n),
-                    UIAction::Noop => {},
-                    UIAction::Quit=> break 'mainloop,
-                };
+                let action = translate_event(maybe_event, app);
+                dispatch(app, action).await;
+                if app.quit {
+                    break 'mainloop;
+                }
             }
         }
     }
 
+    // commands don't outlive us, nor does anything they started
+    for pane in &app.panes {
+        pane.watcher.lock().await.stop();
+    }
+
     Ok(())
 }
 
-fn translate_event(event: crossterm::Result<Event>) -> UIAction {
+fn translate_event(event: crossterm::Result<Event>, app: &mut App) -> UIAction {
     use UIAction::*;
 
     match event {
-        Ok(Event::Key(KeyEvent { code, .. })) => {
-            // q pressed, quit
-            match code {
-                KeyCode::Char(x) if x.is_numeric() => SwitchTabs(x.to_digit(10).unwrap() as usize),
-                KeyCode::Char('q') => Quit,
-                _ => Noop,
-            }
+        Ok(Event::Key(key)) => {
+            app.message = None;
+            let mode = app.mode();
+            app.keymap.press(mode, Key::from(key))
         }
+        Ok(Event::Mouse(MouseEvent {
+            kind, column, row, ..
+        })) if app.prompt.is_none() => match kind {
+            MouseEventKind::ScrollUp => MouseScroll {
+                up: true,
+                column,
+                row,
+            },
+            MouseEventKind::ScrollDown => MouseScroll {
+                up: false,
+                column,
+                row,
+            },
+            _ => Noop,
+        },
         Ok(_) => Noop,
         Err(e) => {
             println!("Error: {:?}", e);
@@ -96,19 +148,217 @@ fn translate_event(event: crossterm::Result<Event>) -> UIAction {
     }
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &App, tails: &[MutexGuard<'_, FileWatcher>]) {
+async fn dispatch(app: &mut App, action: UIAction) {
+    match action {
+        UIAction::SwitchTabs(n) => app.move_to_tab(n),
+        UIAction::CycleLayout => app.cycle_layout(),
+        UIAction::ToggleMerged => app.merged = !app.merged,
+        UIAction::FocusNext => app.focus_next(),
+        UIAction::FocusPrev => app.focus_prev(),
+        UIAction::Restart => {
+            if let Some(pane) = app.focused() {
+                pane.watcher.lock().await.restart();
+            }
+        }
+        UIAction::Kill => {
+            if let Some(pane) = app.focused() {
+                pane.watcher.lock().await.kill();
+            }
+        }
+        UIAction::Clear => app.clear().await,
+        UIAction::Help => app.help = app.help.xor(Some(0)),
+        // the help scrolls while it's open. It's clamped when drawn
+        UIAction::ScrollUp | UIAction::PageUp if app.help.is_some() => {
+            let lines = if action == UIAction::PageUp {
+                HELP_PAGE
+            } else {
+                1
+            };
+            app.help = app.help.map(|offset| offset.saturating_sub(lines));
+        }
+        UIAction::ScrollDown | UIAction::PageDown if app.help.is_some() => {
+            let lines = if action == UIAction::PageDown {
+                HELP_PAGE
+            } else {
+                1
+            };
+            app.help = app.help.map(|offset| offset.saturating_add(lines));
+        }
+        // while selecting, the cursor moves and the view follows it
+        UIAction::ScrollUp
+        | UIAction::ScrollDown
+        | UIAction::PageUp
+        | UIAction::PageDown
+        | UIAction::ScrollTop
+        | UIAction::Follow
+            if app.mode() == Mode::Select =>
+        {
+            if let Some(view) = app.focused_view_mut() {
+                let page = view.page() as isize;
+                view.move_cursor(match action {
+                    UIAction::ScrollUp => 1,
+                    UIAction::ScrollDown => -1,
+                    UIAction::PageUp => page,
+                    UIAction::PageDown => -page,
+                    UIAction::ScrollTop => isize::MAX,
+                    _ => isize::MIN,
+                });
+            }
+        }
+        UIAction::ScrollUp => app.focused_view_mut().map_or((), |v| v.scroll_up(1)),
+        UIAction::ScrollDown => app.focused_view_mut().map_or((), |v| v.scroll_down(1)),
+        UIAction::PageUp => app.focused_view_mut().map_or((), View::page_up),
+        UIAction::PageDown => app.focused_view_mut().map_or((), View::page_down),
+        UIAction::ScrollTop => app.focused_view_mut().map_or((), View::scroll_to_top),
+        UIAction::Follow => app.focused_view_mut().map_or((), View::follow),
+        UIAction::TogglePause => app.focused_view_mut().map_or((), View::toggle_pause),
+        UIAction::MouseScroll { up, column, row } => {
+            if let Some(view) = app.view_at(column, row) {
+                if up {
+                    view.scroll_up(MOUSE_SCROLL);
+                } else {
+                    view.scroll_down(MOUSE_SCROLL);
+                }
+            }
+        }
+        UIAction::Search => app.start_search(),
+        UIAction::NextMatch => app
+            .focused_view_mut()
+            .and_then(|v| v.search.as_mut())
+            .map_or((), Search::older),
+        UIAction::PrevMatch => app
+            .focused_view_mut()
+            .and_then(|v| v.search.as_mut())
+            .map_or((), Search::newer),
+        UIAction::ClearSearch => app.focused_view_mut().map_or((), |v| v.search = None),
+        UIAction::Filter => app.start_filter(false),
+        UIAction::Exclude => app.start_filter(true),
+        UIAction::ToggleFilters => app
+            .focused_view_mut()
+            .map_or((), |v| v.filters.disabled = !v.filters.disabled),
+        UIAction::PopFilter => app.focused_view_mut().map_or((), |v| {
+            v.filters.pop();
+        }),
+        UIAction::ToggleRaw => app.focused_view_mut().map_or((), |v| v.raw = !v.raw),
+        UIAction::FilterApp => app.filter_on_selected("app", false).await,
+        UIAction::ExcludeApp => app.filter_on_selected("app", true).await,
+        UIAction::Fold => app.toggle_fold().await,
+        UIAction::FoldAll => app.focused_view_mut().map_or((), View::toggle_folds),
+        UIAction::Select => app.toggle_select(),
+        UIAction::ToggleDetail => app.detail = app.detail.xor(Some(0)),
+        UIAction::DetailUp => app.detail = app.detail.map(|offset| offset.saturating_sub(1)),
+        UIAction::DetailDown => app.detail = app.detail.map(|offset| offset.saturating_add(1)),
+        UIAction::Command => app.start_command(),
+        UIAction::PromptType(c) => app.edit_prompt(|p| p.query.push(c)),
+        UIAction::PromptErase => app.edit_prompt(|p| {
+            p.query.pop();
+        }),
+        UIAction::PromptToggleRegex => app.edit_prompt(|p| p.regex = !p.regex),
+        UIAction::PromptComplete => app.complete_prompt(),
+        UIAction::PromptSubmit => {
+            if let Some(command) = app.submit_prompt() {
+                run_command(app, &command).await;
+            }
+        }
+        UIAction::PromptCancel => app.cancel_prompt(),
+        UIAction::Quit => app.quit = true,
+        UIAction::Noop => {}
+    }
+}
+
+/// Runs a `:` command, reporting how it went in the status line
+async fn run_command(app: &mut App, line: &str) {
+    if line.trim().is_empty() {
+        return;
+    }
+    let command = match Command::parse(line) {
+        Ok(command) => command,
+        Err(e) => {
+            app.message = Some(e);
+            return;
+        }
+    };
+
+    let result = match command {
+        Command::Open(path) => app.open(&path).await,
+        Command::Run(command) => app.run(&command).await,
+        Command::Close => {
+            app.close_focused().await;
+            Ok(())
+        }
+        Command::Filter { query, exclude } => {
+            match app.focused_view_mut() {
+                Some(view) => match query {
+                    Some(query) => match Filter::parse(&query, true, exclude) {
+                        Ok(filter) => view.filters.push(filter),
+                        Err(e) => app.message = Some(format!("invalid regex: {}", e)),
+                    },
+                    None => view.filters.clear(),
+                },
+                None => app.message = Some("no pane to filter".into()),
+            }
+            Ok(())
+        }
+        Command::Export(path) => app.export(&path).await.map(|n| {
+            app.message = Some(format!("exported {} lines to {}", n, path));
+        }),
+        Command::Layout(mode) => {
+            app.layout = mode;
+            app.merged = false;
+            Ok(())
+        }
+        Command::Set(Setting::Wrap(wrap)) => {
+            app.wrap = wrap;
+            Ok(())
+        }
+        Command::Action(action) => {
+            Box::pin(dispatch(app, action)).await;
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        app.message = Some(format!("{:#}", e));
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App, tails: &[MutexGuard<'_, dyn Watcher + Send>]) {
     let chunks = Layout::default()
-        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
         .split(f.size());
 
-    let titles = vec![
-        Spans::from(vec![Span::raw("All")]),
-        Spans::from(vec![Span::raw("File 1")]),
-        Spans::from(vec![Span::raw("File 2")]),
-    ];
+    let names = pane_names(tails);
+    let visible = app.visible_panes();
+
+    let mut titles = vec![Spans::from(vec![Span::raw("All")])];
+    for (i, name) in names.iter().enumerate() {
+        let mut title = vec![Span::raw(name.clone())];
+        let unread = tails[i]
+            .history()
+            .total_elements()
+            .saturating_sub(app.panes[i].seen);
+        if unread > 0 && !visible.contains(&i) {
+            title.push(Span::styled(
+                format!(" +{}", unread),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        titles.push(Spans::from(title));
+    }
 
     let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title("Tabs"))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Tabs [{}]", view_name(app))),
+        )
         .select(app.tab)
         .style(Style::default().fg(Color::Cyan))
         .highlight_style(
@@ -118,39 +368,571 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &App, tails: &[MutexGuard<'_, FileWatch
         );
     f.render_widget(tabs, chunks[0]);
 
+    // the details of the selected line go below the panes
+    let body = if app.detail.is_some() && app.mode() == Mode::Select {
+        let chunks = Layout::default()
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(chunks[1]);
+        draw_detail(f, chunks[1], app, tails);
+        chunks[0]
+    } else {
+        chunks[1]
+    };
+
+    app.regions.clear();
     match app.tab {
-        0 => draw_all(f, chunks[1], tails),
-        n => draw_single(f, chunks[1], &tails[n - 1], format!("File {}", n)),
+        0 if app.merged => {
+            let highlights: Vec<_> = app.panes.iter().map(|p| &p.highlights[..]).collect();
+            draw_merged(
+                f,
+                body,
+                &mut app.merged_view,
+                &Look {
+                    theme: &app.theme,
+                    wrap: app.wrap,
+                },
+                &highlights,
+                tails,
+                &names,
+            );
+            app.regions.push((None, body));
+        }
+        0 => draw_all(f, body, app, tails, &names),
+        n => {
+            draw_single(
+                f,
+                body,
+                &mut app.panes[n - 1],
+                &Look {
+                    theme: &app.theme,
+                    wrap: app.wrap,
+                },
+                &tails[n - 1],
+                names[n - 1].clone(),
+                false,
+            );
+            app.regions.push((Some(n - 1), body));
+        }
     };
+
+    draw_status(f, chunks[2], app, tails.get(app.focus));
+
+    if app.help.is_some() {
+        draw_help(f, app);
+    }
 }
 
-fn draw_all<B: Backend>(f: &mut Frame<B>, area: Rect, tails: &[MutexGuard<'_, FileWatcher>]) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-        .split(area);
+fn pane_names(tails: &[MutexGuard<'_, dyn Watcher + Send>]) -> Vec<String> {
+    let paths: Vec<_> = tails.iter().filter_map(|t| t.path()).collect();
+    let mut shortened = titles::unique_suffixes(&paths).into_iter();
+
+    tails
+        .iter()
+        .map(|t| match t.path() {
+            Some(_) => shortened.next().unwrap(),
+            None => t.name(),
+        })
+        .collect()
+}
+
+fn draw_all<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    app: &mut App,
+    tails: &[MutexGuard<'_, dyn Watcher + Send>],
+    names: &[String],
+) {
+    let focus_shown = tails.len() > 1;
+    let look = Look {
+        theme: &app.theme,
+        wrap: app.wrap,
+    };
+
+    for (i, rect) in layout::arrange(area, tails.len(), app.focus, app.layout) {
+        let focused = focus_shown && i == app.focus;
+        let pane = &mut app.panes[i];
+        draw_single(f, rect, pane, &look, &tails[i], names[i].clone(), focused);
+        app.regions.push((Some(i), rect));
+    }
+}
+
+fn draw_merged<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    view: &mut View,
+    look: &Look,
+    highlights: &[&[Rule]],
+    tails: &[MutexGuard<'_, dyn Watcher + Send>],
+    names: &[String],
+) {
+    let lines = MergedRev::new(tails.iter().map(|t| t.history().rev_iter()));
+    let height = area.height.saturating_sub(2) as usize;
+    let (window, hidden) = visible_lines(view, lines, height);
+
+    let text: Vec<_> = window
+        .lines
+        .iter()
+        .map(|line| {
+            let mut spans = vec![Span::styled(
+                format!("[{}] ", names[line.source]),
+                Style::default().fg(source_color(line.source)),
+            )];
+            spans.extend(highlight(line, highlights[line.source], view).0);
+            Spans::from(spans)
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(pane_title("All".into(), view, &window, hidden))
+        .borders(Borders::ALL)
+        .border_style(look.theme.border);
 
-    // f.render_widget(Vg)
-    for (i, tail) in tails.iter().enumerate() {
-        draw_single(f, chunks[i], tail, format!("File {}", i + 1));
+    f.render_widget(look.paragraph(text, block), area);
+}
+
+/// What every pane looks like
+struct Look<'a> {
+    theme: &'a Theme,
+    wrap: bool,
+}
+
+impl Look<'_> {
+    fn paragraph<'t>(&self, text: Vec<Spans<'t>>, block: Block<'t>) -> Paragraph<'t> {
+        let paragraph = Paragraph::new(text).block(block).style(self.theme.text);
+        if self.wrap {
+            paragraph.wrap(Wrap { trim: true })
+        } else {
+            paragraph
+        }
+    }
+}
+
+fn source_color(i: usize) -> Color {
+    const COLORS: [Color; 6] = [
+        Color::Cyan,
+        Color::Magenta,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Red,
+    ];
+    COLORS[i % COLORS.len()]
+}
+
+fn view_name(app: &App) -> &'static str {
+    if app.merged {
+        "merged"
+    } else {
+        app.layout.name()
     }
 }
 
 fn draw_single<B: Backend>(
     f: &mut Frame<B>,
     area: Rect,
-    tail: &MutexGuard<'_, FileWatcher>,
+    pane: &mut Pane,
+    look: &Look,
+    tail: &MutexGuard<'_, dyn Watcher + Send>,
     title: String,
+    focused: bool,
 ) {
-    let text: Vec<_> = tail
-        .iter_tail(area.height as usize - 2)
-        .map(|l| l.clone())
+    let lines = tail.history().rev_iter().map(|l| (0, l));
+    let view = &mut pane.view;
+    let height = area.height.saturating_sub(2) as usize;
+    let (window, hidden) = visible_lines(view, lines, height);
+    let mut text: Vec<_> = window
+        .lines
+        .iter()
+        .map(|l| highlight(l, &pane.highlights, view))
+        .collect();
+
+    let title = match tail.status() {
+        Some(status) => {
+            if text.is_empty() {
+                text.push(Spans::from(Span::styled(
+                    format!("{}...", status),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+            format!("{} ({})", title, status)
+        }
+        None => title,
+    };
+
+    let border_style = if focused {
+        look.theme.focused
+    } else {
+        look.theme.border
+    };
+
+    let block = Block::default()
+        .title(pane_title(title, view, &window, hidden))
+        .borders(Borders::ALL)
+        .border_style(border_style);
+
+    f.render_widget(look.paragraph(text, block), area);
+}
+
+/// Picks the lines to show out of `lines`, given newest first along with the index of their
+/// source. Also returns how many lines the filters hid
+fn visible_lines<'a>(
+    view: &mut View,
+    lines: impl Iterator<Item = (usize, &'a Line)>,
+    height: usize,
+) -> (Window<Shown<'a>>, usize) {
+    let lines = lines
+        .map(|(source, line)| Shown::new(source, line, view.raw))
         .collect();
+    let (lines, hidden) = group::keep(lines, &view.filters);
+
+    // matches can be in folded records, which are unfolded to show them
+    let jump = view
+        .search
+        .as_mut()
+        .and_then(|s| s.update(lines.iter().map(|l| (l.line.seq, l.text()))));
+    if let Some(seq) = jump {
+        if let Some(line) = lines.iter().find(|l| l.line.seq == seq) {
+            view.unfold(line.line.group);
+        }
+        view.reveal(seq);
+    }
+    let lines = group::fold(lines, view);
+
+    let window = view.window(lines.into_iter().map(|l| (l.line.seq, l)), height);
+    (window, hidden)
+}
+
+fn pane_title<T>(title: String, view: &View, window: &Window<T>, hidden: usize) -> Spans<'static> {
+    let mut spans = vec![Span::raw(title)];
+
+    for filter in view.filters.iter() {
+        let (sign, color) = if filter.exclude {
+            ('-', Color::Red)
+        } else {
+            ('+', Color::Green)
+        };
+        let color = if view.filters.disabled {
+            Color::DarkGray
+        } else {
+            color
+        };
+        spans.push(Span::styled(
+            format!(" {}{}", sign, filter.matcher),
+            Style::default().fg(color),
+        ));
+    }
+    if view.filters.disabled && !view.filters.is_empty() {
+        spans.push(Span::styled(" (off)", Style::default().fg(Color::DarkGray)));
+    } else if hidden > 0 {
+        spans.push(Span::styled(
+            format!(" ({} hidden)", hidden),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    if view.raw {
+        spans.push(Span::styled(" [raw]", Style::default().fg(Color::DarkGray)));
+    }
+    if view.is_paused() {
+        let indicator = match window.new_lines {
+            0 => " [paused]".into(),
+            1 => " [paused, 1 new line]".into(),
+            n => format!(" [paused, {} new lines]", n),
+        };
+        spans.push(Span::styled(
+            indicator,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    Spans::from(spans)
+}
+
+/// Layers the styles of highlight rules, search matches and the cursor on top of the ones of the
+/// line
+fn highlight(line: &Shown, rules: &[Rule], view: &View) -> Spans<'static> {
+    let mut ranges = highlight::ranges(rules, line.text());
+
+    let matches = view
+        .search
+        .as_ref()
+        .map(|s| s.ranges(line.line.seq))
+        .unwrap_or_default();
+    ranges.extend(matches.into_iter().map(|(range, current)| {
+        let style = if current {
+            Style::default().fg(Color::Black).bg(Color::Yellow)
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        };
+        (range, style)
+    }));
+
+    if view.cursor() == Some(line.line.seq) {
+        ranges.push((0..line.text().len(), Style::default().bg(Color::DarkGray)));
+    }
+
+    let mut spans = spans::patch(line.spans(), &ranges);
+    if line.folded > 0 {
+        spans.0.push(Span::styled(
+            format!(" [+{} lines]", line.folded),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    spans
+}
+
+/// The prompt while something is being typed, or feedback and the state of the focused pane's
+/// search, next to the stats of the focused source
+fn draw_status<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    app: &App,
+    tail: Option<&MutexGuard<'_, dyn Watcher + Send>>,
+) {
+    let hint = Style::default().fg(Color::DarkGray);
+    let mut spans = vec![];
+
+    // the prompt gets the whole line, as paths and completions can be long
+    let area = match tail.filter(|_| app.prompt.is_none()) {
+        Some(tail) => {
+            let stats = source_stats(app, tail);
+            let width = (stats.width() as u16).min(area.width);
+            let right = Rect {
+                x: area.x + area.width - width,
+                width,
+                ..area
+            };
+            f.render_widget(Paragraph::new(stats), right);
+            Rect {
+                width: area.width - width,
+                ..area
+            }
+        }
+        None => area,
+    };
+
+    if let Some(prompt) = &app.prompt {
+        let typed = format!("{}{}", prompt.label(), prompt.query);
+        f.set_cursor(area.x + typed.chars().count() as u16, area.y);
+        spans.push(Span::raw(typed));
+
+        if prompt.invalid {
+            spans.push(Span::styled(
+                "  invalid regex",
+                Style::default().fg(Color::Red),
+            ));
+        }
+        if prompt.candidates.len() > 1 {
+            spans.push(Span::styled(
+                format!("  {}", prompt.candidates.join("  ")),
+                hint,
+            ));
+        }
+        if app.mode() == Mode::Search {
+            let kind = if prompt.regex { "regex" } else { "text" };
+            spans.push(Span::styled(format!("  [{}]", kind), hint));
+        }
+    } else if let Some(message) = &app.message {
+        spans.push(Span::styled(
+            message.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    } else if app.mode() == Mode::Select {
+        spans.push(Span::styled("-- select --", hint));
+    } else if let Some(search) = app.focused_view().and_then(|v| v.search.as_ref()) {
+        spans.push(Span::raw(format!("/{}", search.query)));
+        spans.push(Span::styled(format!("  {}", search.status()), hint));
+    }
+
+    // start of a key sequence
+    let pending: String = app.keymap.pending().iter().map(|k| k.to_string()).collect();
+    if !pending.is_empty() {
+        spans.push(Span::styled(format!("  {}", pending), hint));
+    }
+
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+/// Where the focused source is at: what it is, how much of it was read and kept, how fast lines
+/// come in and whether the pane follows them
+fn source_stats(app: &App, tail: &MutexGuard<'_, dyn Watcher + Send>) -> Spans<'static> {
+    let hint = Style::default().fg(Color::DarkGray);
+    let separator = || Span::styled(" | ", hint);
+    let mut spans = vec![Span::raw(tail.name())];
+
+    if let (Some(size), Some(pos)) = (tail.size(), tail.pos()) {
+        spans.push(separator());
+        spans.push(Span::raw(format!(
+            "{} of {}",
+            stats::format_size(pos),
+            stats::format_size(size)
+        )));
+    }
+
+    let history = tail.history();
+    spans.push(separator());
+    spans.push(Span::raw(format!(
+        "{}/{} lines",
+        history.len(),
+        history.total_elements()
+    )));
+    if history.has_wrapped() {
+        spans.push(Span::styled(" wrapped", Style::default().fg(Color::Yellow)));
+    }
+
+    spans.push(separator());
+    spans.push(Span::raw(format!(
+        "{:.1}/s",
+        app.panes[app.focus].rate.per_sec()
+    )));
+
+    spans.push(separator());
+    spans.push(match app.focused_view() {
+        Some(view) if view.is_paused() => {
+            Span::styled("paused ", Style::default().fg(Color::Yellow))
+        }
+        _ => Span::styled("following ", Style::default().fg(Color::Green)),
+    });
+
+    Spans::from(spans)
+}
+
+/// The selected line in full: its fields, its JSON pretty-printed and the text as it was read
+fn draw_detail<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    app: &mut App,
+    tails: &[MutexGuard<'_, dyn Watcher + Send>],
+) {
+    let line = app.focused_view().and_then(View::cursor).and_then(|seq| {
+        tails
+            .iter()
+            .find_map(|t| t.history().rev_iter().find(|l| l.seq == seq))
+    });
+    let text = line.map(detail).unwrap_or_default();
+
+    // lines of text can take several on screen
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let height: usize = text.iter().map(|l| l.width().max(1).div_ceil(width)).sum();
+    let max_offset = height.saturating_sub(area.height.saturating_sub(2) as usize) as u16;
+    let offset = app.detail.unwrap_or(0).min(max_offset);
+    app.detail = Some(offset);
+
+    let paragraph = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title("Details")
+                .borders(Borders::ALL)
+                .border_style(app.theme.focused),
+        )
+        .style(app.theme.text)
+        .wrap(Wrap { trim: false })
+        .scroll((offset, 0));
+    f.render_widget(paragraph, area);
+}
+
+fn detail(line: &Line) -> Vec<Spans<'static>> {
+    let heading = |title: &str| {
+        Spans::from(Span::styled(
+            title.to_string(),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ))
+    };
+    let mut text = vec![];
+
+    if let Some(record) = &line.record {
+        text.push(heading("fields"));
+        let width = record
+            .fields
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (name, value) in &record.fields {
+            text.push(Spans::from(vec![
+                Span::styled(
+                    format!("  {:width$}  ", name, width = width),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(value.clone()),
+            ]));
+        }
+        text.push(Spans::default());
+    }
+
+    if let Some(json) = json::pretty(&line.text) {
+        text.push(heading("json"));
+        text.extend(json.lines().map(|l| Spans::from(format!("  {}", l))));
+        text.push(Spans::default());
+    }
+
+    text.push(heading("raw"));
+    text.push(line.spans.clone());
+    text
+}
+
+/// Popup listing the bindings of every mode, on top of everything else
+fn draw_help<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let help = app.keymap.help();
+    let width = help
+        .iter()
+        .flat_map(|(_, lines)| lines)
+        .map(|l| l.keys.join(" ").chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut text = vec![];
+    for (mode, lines) in help {
+        if !text.is_empty() {
+            text.push(Spans::default());
+        }
+        text.push(Spans::from(Span::styled(
+            format!("{} mode", mode.name()),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )));
+        for line in lines {
+            text.push(Spans::from(vec![
+                Span::styled(
+                    format!("  {:width$}  ", line.keys.join(" "), width = width),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(line.description),
+            ]));
+        }
+    }
+
+    let screen = f.size();
+    let area = Rect {
+        width: (width as u16 + 50).min(screen.width),
+        height: (text.len() as u16 + 2).min(screen.height),
+        ..screen
+    };
+    let area = Rect {
+        x: (screen.width - area.width) / 2,
+        y: (screen.height - area.height) / 2,
+        ..area
+    };
+
+    let max_offset = (text.len() as u16 + 2).saturating_sub(area.height);
+    let offset = app.help.unwrap_or(0).min(max_offset);
+    app.help = Some(offset);
 
-    let block = Paragraph::new(text)
-        .block(Block::default().title(title).borders(Borders::ALL))
-        .style(Style::default().fg(Color::White).bg(Color::Black))
-        .wrap(Wrap { trim: true });
+    let popup = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title("Help")
+                .borders(Borders::ALL)
+                .border_style(app.theme.focused),
+        )
+        .style(app.theme.text)
+        .scroll((offset, 0));
 
-    f.render_widget(block, area);
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
 }
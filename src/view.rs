@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+
+use crate::{filter::Filters, search::Search};
+
+/// records longer than this many lines can be folded
+const FOLD_LINES: usize = 5;
+
+/// Scroll, search, filtering and selection state of a pane
+#[derive(Default)]
+pub struct View {
+    /// lines scrolled up from the bottom, not counting the ones held back while paused
+    offset: usize,
+    /// while paused, the newest line at the time. lines arriving after it are held back
+    paused_at: Option<u64>,
+    /// newest line, and number of lines that fit on screen, as of the last render
+    newest: Option<u64>,
+    height: usize,
+    /// line to bring on screen on the next render
+    reveal: Option<u64>,
+    /// selected line, while selecting, and how far it was from the newest line on the last render
+    cursor: Option<u64>,
+    cursor_index: usize,
+    /// lines to move the cursor up by on the next render
+    cursor_moves: isize,
+    /// newest line on screen as of the last render
+    bottom: Option<u64>,
+    pub search: Option<Search>,
+    pub filters: Filters,
+    /// whether structured lines are shown as they were read rather than rendered from their fields
+    pub raw: bool,
+    /// records whose folding was toggled, by their first line
+    folds: HashSet<u64>,
+    /// whether long records are folded, except for the toggled ones. they're shown in full until
+    /// asked otherwise
+    folded: bool,
+}
+
+/// What a view shows
+pub struct Window<T> {
+    /// oldest first
+    pub lines: Vec<T>,
+    /// lines that arrived since the view was paused
+    pub new_lines: usize,
+}
+
+impl View {
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        self.pause();
+        self.offset = self.offset.saturating_add(n);
+    }
+
+    /// Scrolling past the bottom resumes following
+    pub fn scroll_down(&mut self, n: usize) {
+        if self.offset > n {
+            self.offset -= n;
+        } else {
+            self.follow();
+        }
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.page());
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.page());
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.scroll_up(usize::MAX);
+    }
+
+    pub fn follow(&mut self) {
+        self.offset = 0;
+        self.paused_at = None;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused() {
+            self.follow();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Scrolls just enough for the given line to be on screen on the next render
+    pub fn reveal(&mut self, seq: u64) {
+        self.reveal = Some(seq);
+    }
+
+    pub fn cursor(&self) -> Option<u64> {
+        self.cursor
+    }
+
+    /// Starts selecting lines from the newest one on screen. The view stops following new lines
+    pub fn select(&mut self) {
+        self.pause();
+        self.cursor = Some(self.bottom.unwrap_or(u64::MAX));
+        self.cursor_moves = 0;
+    }
+
+    pub fn unselect(&mut self) {
+        self.cursor = None;
+        self.cursor_moves = 0;
+    }
+
+    /// Moves the cursor up by `n` lines, or down for negative numbers, scrolling to keep it on
+    /// screen
+    pub fn move_cursor(&mut self, n: isize) {
+        self.cursor_moves = self.cursor_moves.saturating_add(n);
+    }
+
+    /// Whether a record of `len` lines is folded down to its first one
+    pub fn is_folded(&self, group: u64, len: usize) -> bool {
+        len > FOLD_LINES && self.folded != self.folds.contains(&group)
+    }
+
+    pub fn toggle_fold(&mut self, group: u64) {
+        if !self.folds.remove(&group) {
+            self.folds.insert(group);
+        }
+    }
+
+    /// Makes sure a record is shown in full, if it's long enough to be folded
+    pub fn unfold(&mut self, group: u64) {
+        if self.is_folded(group, usize::MAX) {
+            self.toggle_fold(group);
+        }
+    }
+
+    /// Folds or unfolds every long record
+    pub fn toggle_folds(&mut self) {
+        self.folded = !self.folded;
+        self.folds.clear();
+    }
+
+    fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.newest.unwrap_or(0));
+        }
+    }
+
+    pub fn page(&self) -> usize {
+        self.height.saturating_sub(1).max(1)
+    }
+
+    /// Picks the lines to show out of `lines`, given newest first along with their sequence
+    /// numbers
+    pub fn window<T>(&mut self, lines: impl Iterator<Item = (u64, T)>, height: usize) -> Window<T> {
+        let lines: Vec<_> = lines.collect();
+
+        self.newest = lines.iter().map(|(seq, _)| *seq).max();
+        self.height = height;
+        if let Some(seq) = self.reveal.take() {
+            self.scroll_to(seq, &lines);
+        }
+
+        let mut new_lines = 0;
+        let mut held = vec![];
+        for (seq, line) in lines {
+            match self.paused_at {
+                Some(paused_at) if seq > paused_at => new_lines += 1,
+                _ => held.push((seq, line)),
+            }
+        }
+        self.move_cursor_within(&held);
+
+        // scrolling past the top stops there
+        self.offset = self.offset.min(held.len().saturating_sub(height));
+        self.bottom = held.get(self.offset).map(|(seq, _)| *seq);
+
+        let mut lines: Vec<_> = held
+            .into_iter()
+            .skip(self.offset)
+            .take(height)
+            .map(|(_, line)| line)
+            .collect();
+        lines.reverse();
+
+        Window { lines, new_lines }
+    }
+
+    // the cursor stays on the line it was on, or takes the place of that one if it's gone. lines
+    // of merged panes aren't in the order of their sequence numbers, which can't tell neighbours
+    fn move_cursor_within<T>(&mut self, lines: &[(u64, T)]) {
+        let (cursor, last) = match (self.cursor, lines.len().checked_sub(1)) {
+            (Some(cursor), Some(last)) => (cursor, last),
+            _ => return,
+        };
+        let index = match lines.iter().position(|(seq, _)| *seq == cursor) {
+            Some(index) => index,
+            // selecting starts from the newest line on screen
+            None if cursor == u64::MAX => 0,
+            None => self.cursor_index.min(last),
+        };
+        let moves = std::mem::take(&mut self.cursor_moves);
+        let index = (index as isize)
+            .saturating_add(moves)
+            .clamp(0, last as isize) as usize;
+
+        self.cursor = Some(lines[index].0);
+        self.cursor_index = index;
+        if index < self.offset {
+            self.offset = index;
+        } else if index >= self.offset + self.height {
+            self.offset = index + 1 - self.height;
+        }
+    }
+
+    fn scroll_to<T>(&mut self, target: u64, lines: &[(u64, T)]) {
+        // catch up with the lines held back if needed
+        if self.paused_at.is_some_and(|paused_at| target > paused_at) {
+            self.paused_at = self.newest;
+        }
+
+        let paused_at = self.paused_at;
+        let index = lines
+            .iter()
+            .filter(|(seq, _)| paused_at.is_none_or(|paused_at| *seq <= paused_at))
+            .position(|(seq, _)| *seq == target);
+
+        if let Some(index) = index {
+            if !(self.offset..self.offset + self.height).contains(&index) {
+                self.pause();
+                self.offset = index.saturating_sub(self.height / 2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::View;
+
+    // lines numbered by their sequence numbers, given newest first
+    fn render(view: &mut View, total: u64) -> (Vec<u64>, usize) {
+        let window = view.window((0..total).rev().map(|seq| (seq, seq)), 3);
+        (window.lines, window.new_lines)
+    }
+
+    #[test]
+    fn follows_by_default() {
+        let mut view = View::default();
+        assert_eq!(render(&mut view, 10), (vec![7, 8, 9], 0));
+        assert_eq!(render(&mut view, 12), (vec![9, 10, 11], 0));
+    }
+
+    #[test]
+    fn pauses_when_scrolling_up() {
+        let mut view = View::default();
+        render(&mut view, 10);
+
+        view.scroll_up(2);
+        assert_eq!(render(&mut view, 10), (vec![5, 6, 7], 0));
+        // new lines don't move the view
+        assert_eq!(render(&mut view, 14), (vec![5, 6, 7], 4));
+
+        view.scroll_down(1);
+        assert_eq!(render(&mut view, 14), (vec![6, 7, 8], 4));
+
+        // going past the bottom resumes following
+        view.scroll_down(5);
+        assert!(!view.is_paused());
+        assert_eq!(render(&mut view, 14), (vec![11, 12, 13], 0));
+    }
+
+    #[test]
+    fn stops_at_the_top() {
+        let mut view = View::default();
+        render(&mut view, 10);
+
+        view.scroll_to_top();
+        assert_eq!(render(&mut view, 10), (vec![0, 1, 2], 0));
+
+        view.page_down();
+        assert_eq!(render(&mut view, 10), (vec![2, 3, 4], 0));
+    }
+
+    #[test]
+    fn reveals_lines_out_of_view() {
+        let mut view = View::default();
+        render(&mut view, 10);
+
+        // already on screen
+        view.reveal(8);
+        assert_eq!(render(&mut view, 10), (vec![7, 8, 9], 0));
+        assert!(!view.is_paused());
+
+        view.reveal(2);
+        assert_eq!(render(&mut view, 10), (vec![1, 2, 3], 0));
+        assert!(view.is_paused());
+
+        // lines held back while paused
+        assert_eq!(render(&mut view, 20), (vec![1, 2, 3], 10));
+        view.reveal(15);
+        assert_eq!(render(&mut view, 20), (vec![14, 15, 16], 0));
+    }
+
+    #[test]
+    fn moves_the_cursor_within_the_screen() {
+        let mut view = View::default();
+        render(&mut view, 10);
+
+        view.select();
+        assert_eq!(render(&mut view, 12), (vec![7, 8, 9], 2));
+        assert_eq!(view.cursor(), Some(9));
+
+        view.move_cursor(2);
+        assert_eq!(render(&mut view, 12), (vec![7, 8, 9], 2));
+        assert_eq!(view.cursor(), Some(7));
+
+        // scrolls once the cursor reaches the top
+        view.move_cursor(1);
+        assert_eq!(render(&mut view, 12), (vec![6, 7, 8], 2));
+        assert_eq!(view.cursor(), Some(6));
+
+        view.move_cursor(isize::MIN);
+        assert_eq!(render(&mut view, 12), (vec![7, 8, 9], 2));
+        assert_eq!(view.cursor(), Some(9));
+    }
+
+    #[test]
+    fn keeps_the_cursor_on_its_line_in_merged_order() {
+        // lines of two sources, ordered by their time rather than by when they were read
+        let render = |view: &mut View, seqs: &[u64]| {
+            view.window(seqs.iter().rev().map(|seq| (*seq, *seq)), 3)
+                .lines
+        };
+        let mut view = View::default();
+        render(&mut view, &[0, 3, 1, 4, 2, 5]);
+
+        view.select();
+        view.move_cursor(2);
+        assert_eq!(render(&mut view, &[0, 3, 1, 4, 2, 5]), [4, 2, 5]);
+        assert_eq!(view.cursor(), Some(4));
+        assert_eq!(render(&mut view, &[0, 3, 1, 4, 2, 5]), [4, 2, 5]);
+        assert_eq!(view.cursor(), Some(4));
+
+        // takes the place of its line once it's gone
+        render(&mut view, &[0, 3, 1, 2, 5]);
+        assert_eq!(view.cursor(), Some(1));
+    }
+}
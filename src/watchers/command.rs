@@ -0,0 +1,353 @@
+use ansi_to_tui::IntoText;
+use std::{
+    io,
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+    select,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot, Mutex,
+    },
+    task::JoinHandle,
+};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::{
+    circular::CircularBuffer,
+    format::{Parsed, Parser},
+    line::{self, Line},
+};
+
+use super::Watcher;
+
+enum Output {
+    Stdout(String),
+    Stderr(String),
+    Exit(io::Result<ExitStatus>),
+}
+
+/// Runs a shell command and follows its stdout and stderr
+pub struct CommandWatcher {
+    pub command: String,
+    pub history: CircularBuffer<Line>,
+    /// set once the process is gone
+    pub exit: Option<String>,
+    tx: Option<UnboundedSender<()>>,
+    output: Option<UnboundedReceiver<Output>>,
+    kill: Option<oneshot::Sender<()>>,
+    /// process group of the running command, which is what gets killed
+    group: Option<u32>,
+    parser: Parser,
+}
+
+impl CommandWatcher {
+    pub fn new(command: &str, history: usize, parser: Parser) -> anyhow::Result<Arc<Mutex<Self>>> {
+        Ok(Arc::new(Mutex::new(Self {
+            command: command.into(),
+            history: CircularBuffer::new(history),
+            exit: None,
+            tx: None,
+            output: None,
+            kill: None,
+            group: None,
+            parser,
+        })))
+    }
+
+    fn spawn(&mut self) -> anyhow::Result<()> {
+        let Some(tx) = self.tx.clone() else {
+            return Ok(());
+        };
+
+        let mut command = std::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(&self.command)
+            // the terminal's input belongs to us
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // in a group of its own, so that pipelines and whatever else the shell starts can be
+        // killed along with it
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = Command::from(command).kill_on_drop(true).spawn()?;
+        self.group = child.id();
+
+        let (out_tx, out_rx) = mpsc::unbounded_channel();
+        let (kill_tx, kill_rx) = oneshot::channel();
+
+        let readers = [
+            forward_lines(
+                child.stdout.take(),
+                Output::Stdout,
+                out_tx.clone(),
+                tx.clone(),
+            ),
+            forward_lines(
+                child.stderr.take(),
+                Output::Stderr,
+                out_tx.clone(),
+                tx.clone(),
+            ),
+        ];
+
+        tokio::task::spawn(async move {
+            let status = select! {
+                status = child.wait() => status,
+                _ = kill_rx => {
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+
+            // let the last lines through before reporting the exit. processes left running in
+            // the background may keep the pipes open though, so don't wait on them forever
+            let drained = futures::future::join_all(readers.into_iter().flatten());
+            let _ = tokio::time::timeout(Duration::from_secs(1), drained).await;
+
+            let _ = out_tx.send(Output::Exit(status));
+            let _ = tx.send(());
+        });
+
+        self.exit = None;
+        self.output = Some(out_rx);
+        self.kill = Some(kill_tx);
+
+        Ok(())
+    }
+
+    fn push_marker(&mut self, what: &str) {
+        line::push(
+            &mut self.history,
+            Spans::from(Span::styled(
+                format!("--- {} ---", what),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Parsed::default(),
+        );
+    }
+}
+
+fn forward_lines<R, F>(
+    stream: Option<R>,
+    wrap: F,
+    out_tx: UnboundedSender<Output>,
+    tx: UnboundedSender<()>,
+) -> Option<JoinHandle<()>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    F: Fn(String) -> Output + Send + 'static,
+{
+    let stream = stream?;
+
+    Some(tokio::task::spawn(async move {
+        let mut lines = BufReader::new(stream).split(b'\n');
+        while let Ok(Some(line)) = lines.next_segment().await {
+            let line = String::from_utf8_lossy(&line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if out_tx.send(wrap(line.to_string())).is_err() {
+                break;
+            }
+            let _ = tx.send(());
+        }
+    }))
+}
+
+impl Watcher for CommandWatcher {
+    fn start(&mut self, tx: UnboundedSender<()>) -> anyhow::Result<()> {
+        self.tx = Some(tx);
+        self.spawn()
+    }
+
+    fn poll(&mut self) {
+        let Some(mut output) = self.output.take() else {
+            return;
+        };
+
+        while let Ok(out) = output.try_recv() {
+            match out {
+                Output::Stdout(line) => {
+                    for line in line.into_text().unwrap().lines {
+                        let parsed = self.parser.parse(&line::text(&line));
+                        line::push(&mut self.history, line, parsed);
+                    }
+                }
+                // stderr lines get a red gutter, keeping whatever colors they had
+                Output::Stderr(line) => {
+                    for mut line in line.into_text().unwrap().lines {
+                        let parsed = self.parser.parse(&line::text(&line));
+                        line.0
+                            .insert(0, Span::styled("▌", Style::default().fg(Color::Red)));
+                        line::push(&mut self.history, line, parsed);
+                    }
+                }
+                Output::Exit(status) => {
+                    let exit = match status {
+                        Ok(status) => describe(status),
+                        Err(e) => format!("failed: {}", e),
+                    };
+                    self.push_marker(&exit);
+                    self.exit = Some(exit);
+                    // the group id may be reused once the process is reaped
+                    self.group = None;
+                }
+            }
+        }
+
+        self.output = Some(output);
+    }
+
+    fn history(&self) -> &CircularBuffer<Line> {
+        &self.history
+    }
+
+    fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    fn name(&self) -> String {
+        format!("$ {}", self.command)
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(self.exit.clone().unwrap_or_else(|| "running".into()))
+    }
+
+    fn restart(&mut self) {
+        self.kill();
+        self.push_marker("restarted");
+        if let Err(e) = self.spawn() {
+            let exit = format!("failed: {}", e);
+            self.push_marker(&exit);
+            self.exit = Some(exit);
+        }
+    }
+
+    fn kill(&mut self) {
+        #[cfg(unix)]
+        if let Some(group) = self.group.take() {
+            // SAFETY: kill only sends a signal
+            unsafe { libc::kill(-(group as libc::pid_t), libc::SIGKILL) };
+        }
+        if let Some(kill) = self.kill.take() {
+            let _ = kill.send(());
+        }
+    }
+
+    fn stop(&mut self) {
+        self.kill();
+        self.tx = None;
+    }
+}
+
+fn describe(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("killed by signal {}", signal);
+        }
+    }
+
+    match status.code() {
+        Some(code) => format!("exited with {}", code),
+        None => "exited".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandWatcher;
+    use crate::{format::Parser, watchers::Watcher};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn captures_output_and_exit_status() {
+        let watcher =
+            CommandWatcher::new("echo out; echo err >&2; exit 3", 100, Parser::default()).unwrap();
+        let mut watcher = watcher.lock().await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        watcher.start(tx).unwrap();
+
+        while watcher.exit.is_none() {
+            rx.recv().await;
+            watcher.poll();
+        }
+
+        let mut lines: Vec<String> = watcher
+            .history
+            .iter()
+            .map(|l| l.spans.0.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        // stdout and stderr are read concurrently
+        lines[..2].sort();
+        assert_eq!(lines, ["out", "▌err", "--- exited with 3 ---"]);
+        assert_eq!(watcher.status().unwrap(), "exited with 3");
+        assert_eq!(watcher.group, None);
+    }
+
+    #[tokio::test]
+    async fn keeps_reading_past_invalid_utf8() {
+        let watcher =
+            CommandWatcher::new("printf 'a\\377b\\nnext\\n'", 100, Parser::default()).unwrap();
+        let mut watcher = watcher.lock().await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        watcher.start(tx).unwrap();
+
+        while watcher.exit.is_none() {
+            rx.recv().await;
+            watcher.poll();
+        }
+
+        let lines: Vec<&str> = watcher.history.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(lines, ["a\u{fffd}b", "next", "--- exited with 0 ---"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn kills_whatever_the_command_started() {
+        let watcher =
+            CommandWatcher::new("sleep 30 & echo $!; wait", 100, Parser::default()).unwrap();
+        let mut watcher = watcher.lock().await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        watcher.start(tx).unwrap();
+
+        while watcher.history.is_empty() {
+            rx.recv().await;
+            watcher.poll();
+        }
+        let pid = watcher.history.iter().next().unwrap().text.clone();
+
+        watcher.kill();
+        while watcher.exit.is_none() {
+            rx.recv().await;
+            watcher.poll();
+        }
+
+        // gone, or a zombie nobody reaped, once the signal got through
+        let dead = || {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+            let state = stat.rsplit(')').next().unwrap_or_default().trim_start();
+            stat.is_empty() || state.starts_with('Z')
+        };
+        for _ in 0..100 {
+            if dead() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(dead());
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Context;
+use glob::{MatchOptions, Pattern};
+use notify::{
+    event::{CreateKind, ModifyKind, RenameMode},
+    recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _,
+};
+use std::path::{Component, Path, PathBuf};
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Discovery {
+    Added(String),
+    Removed(String),
+}
+
+// `*` shouldn't cross directory boundaries, like in a shell. `**` still does
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A glob pattern, or a directory, whose matching files each get a pane
+pub struct Spec {
+    /// deepest directory that contains every possible match, which is the one being watched
+    root: PathBuf,
+    pattern: Pattern,
+}
+
+impl Spec {
+    /// Returns `None` for a path to a single file
+    pub fn parse(arg: &str) -> anyhow::Result<Option<Self>> {
+        let path = Path::new(arg);
+
+        if path.is_dir() {
+            let pattern = Path::new(&Pattern::escape(arg)).join("**").join("*");
+            return Ok(Some(Self {
+                root: path.into(),
+                pattern: Pattern::new(&pattern.to_string_lossy())?,
+            }));
+        }
+
+        if !arg.contains(['*', '?', '[']) {
+            return Ok(None);
+        }
+
+        let root: PathBuf = path
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+
+        Ok(Some(Self {
+            root,
+            pattern: Pattern::new(arg).with_context(|| format!("invalid pattern {}", arg))?,
+        }))
+    }
+
+    /// Files matching the pattern right now
+    pub fn matches(&self) -> anyhow::Result<Vec<String>> {
+        Ok(glob::glob_with(self.pattern.as_str(), MATCH_OPTIONS)?
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /// Watches the root directory for files matching the pattern being created or removed
+    pub fn watch(self, tx: UnboundedSender<Discovery>) -> anyhow::Result<RecommendedWatcher> {
+        let root = self.root_dir();
+        // notify reports absolute paths. they are mapped back to the way the user wrote them
+        let absolute = std::env::current_dir()?.join(&root);
+
+        let mut watcher = recommended_watcher(move |res| {
+            let Ok(Event { kind, paths, .. }) = res else {
+                return;
+            };
+
+            for path in paths {
+                let Ok(relative) = path.strip_prefix(&absolute) else {
+                    continue;
+                };
+                let path = self.root.join(relative);
+                if !self.pattern.matches_path_with(&path, MATCH_OPTIONS) {
+                    continue;
+                }
+                let path = path.to_string_lossy().into_owned();
+
+                let discovery = match kind {
+                    EventKind::Create(CreateKind::File | CreateKind::Any)
+                    | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Discovery::Added(path),
+                    // files which are renamed away are kept, as they're usually being rotated
+                    EventKind::Remove(..) => Discovery::Removed(path),
+                    _ => continue,
+                };
+                let _ = tx.send(discovery);
+            }
+        })?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .with_context(|| format!("cannot watch directory {}", root.display()))?;
+
+        Ok(watcher)
+    }
+
+    fn root_dir(&self) -> PathBuf {
+        if self.root.as_os_str().is_empty() || self.root.components().eq([Component::CurDir]) {
+            PathBuf::from(".")
+        } else {
+            self.root.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+    use std::path::Path;
+
+    #[test]
+    fn parses_patterns() {
+        assert!(Spec::parse("some/file.log").unwrap().is_none());
+
+        let spec = Spec::parse("logs/*/worker-?.log").unwrap().unwrap();
+        assert_eq!(spec.root, Path::new("logs"));
+
+        let spec = Spec::parse("*.log").unwrap().unwrap();
+        assert_eq!(spec.root_dir(), Path::new("."));
+    }
+
+    #[test]
+    fn watches_directories() {
+        let dir = std::env::temp_dir().join(format!("ninetails-discover-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a.log"), b"").unwrap();
+        std::fs::write(dir.join("nested/b.log"), b"").unwrap();
+
+        let spec = Spec::parse(dir.to_str().unwrap()).unwrap().unwrap();
+        let mut matches = spec.matches().unwrap();
+        matches.sort();
+        assert_eq!(
+            matches,
+            [
+                dir.join("a.log").to_string_lossy(),
+                dir.join("nested/b.log").to_string_lossy()
+            ]
+        );
+
+        let spec = Spec::parse(dir.join("*.log").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            spec.matches().unwrap(),
+            [dir.join("a.log").to_string_lossy()]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
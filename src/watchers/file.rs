@@ -1,38 +1,237 @@
 use ansi_to_tui::IntoText;
+use anyhow::Context;
 use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, Watcher as _};
+use rev_buf_reader::RevBufReader;
 use std::{
-    io::{Read, Seek, SeekFrom},
-    path::Path,
+    ffi::OsString,
+    fs::{File, Metadata},
+    io::{self, BufRead, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
 
 use tokio::sync::{mpsc::UnboundedSender, Mutex};
 
-use crate::circular::CircularBuffer;
+use crate::{
+    circular::CircularBuffer,
+    format::{Parsed, Parser},
+    line::{self, Line},
+};
 
 use super::Watcher;
 
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// number of lines to load from the end of the file on the first read
+    pub backfill: Option<usize>,
+    /// when the file is rotated, read whatever was still appended to the old one before switching
+    pub drain_rotated: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// not read yet
+    Opening,
+    Following,
+    /// the file doesn't exist, yet or anymore
+    Waiting,
+    /// the file is there but reading it failed, with the error, like when permissions are missing
+    Failed(String),
+}
+
 pub struct FileWatcher {
     pub path: String,
-    pub history: CircularBuffer<tui::text::Spans<'static>>,
+    pub history: CircularBuffer<Line>,
     pub pos: u64,
+    /// size of the file the last time it was read
+    pub size: u64,
+    pub status: Status,
+    options: Options,
+    /// the file currently being followed. may no longer be the one at `path` if it was rotated
+    file: Option<File>,
     handle: Option<RecommendedWatcher>,
+    /// directory watched for the file to appear. the nearest existing one if its own is missing
+    dir: Option<PathBuf>,
+    parser: Parser,
 }
 
 impl FileWatcher {
-    pub fn new(file: &str) -> anyhow::Result<Arc<Mutex<Self>>> {
+    pub fn new(
+        file: &str,
+        history: usize,
+        options: Options,
+        parser: Parser,
+    ) -> anyhow::Result<Arc<Mutex<Self>>> {
         Ok(Arc::new(Mutex::new(Self {
             path: file.into(),
-            history: CircularBuffer::new(10000),
+            history: CircularBuffer::new(history),
             pos: 0,
+            size: 0,
+            status: Status::Opening,
+            options,
+            file: None,
             handle: None,
+            dir: None,
+            parser,
         })))
     }
 
-    pub fn iter_tail<'b>(&'b self, n: usize) -> impl Iterator<Item = &tui::text::Spans<'b>> {
-        self.history
-            .iter()
-            .skip(self.history.len().saturating_sub(n))
+    // the watch on the file itself follows the old inode, so it needs to be re-created
+    fn rearm(&mut self) {
+        if let Some(handle) = self.handle.as_mut() {
+            let path = Path::new(&self.path);
+            let _ = handle.unwatch(path);
+            let _ = handle.watch(path, notify::RecursiveMode::NonRecursive);
+        }
+    }
+
+    /// Watches the directory of the file, or its nearest ancestor that exists until that is created
+    fn watch_dir(&mut self) -> notify::Result<()> {
+        let Some(handle) = self.handle.as_mut() else {
+            return Ok(());
+        };
+        let dir = match Path::new(&self.path).parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let nearest = dir
+            .ancestors()
+            .find(|d| d.is_dir())
+            .filter(|d| *d != Path::new(""))
+            .unwrap_or(Path::new("."));
+        if self.dir.as_deref() == Some(nearest) {
+            return Ok(());
+        }
+
+        handle.watch(nearest, notify::RecursiveMode::NonRecursive)?;
+        if let Some(old) = self.dir.replace(nearest.to_path_buf()) {
+            let _ = handle.unwatch(&old);
+        }
+        Ok(())
+    }
+
+    fn follow(&mut self) -> io::Result<()> {
+        if let Some(file) = &self.file {
+            // the path no longer points to the file we have open, either because it was removed
+            // or because it was moved away and a new one created in its place
+            let current = std::fs::metadata(&self.path);
+            let replaced = match &current {
+                Ok(current) => file_id(&file.metadata()?) != file_id(current),
+                Err(_) => true,
+            };
+
+            if replaced {
+                if self.options.drain_rotated {
+                    self.read_new_lines()?;
+                }
+                self.file = None;
+                self.pos = 0;
+
+                current?;
+                self.push_marker("was rotated");
+                self.rearm();
+            }
+        }
+
+        if self.file.is_none() {
+            let mut f = File::open(&self.path)?;
+            if let Some(n) = self.options.backfill.take() {
+                self.pos = tail_offset(&mut f, n)?;
+            }
+            self.file = Some(f);
+        }
+
+        let len = self.file.as_ref().unwrap().metadata()?.len();
+        if len < self.pos {
+            self.push_marker("was truncated");
+            self.pos = 0;
+        }
+
+        let contents = self.read_new_contents()?;
+
+        // the file only counts as back once it could actually be read, so that a path which opens
+        // but fails to read doesn't flip between both markers on every poll
+        let recovered = match self.status {
+            Status::Waiting => Some("was created"),
+            Status::Failed(_) => Some("can be read again"),
+            _ => None,
+        };
+        if let Some(what) = recovered {
+            self.push_marker(what);
+            self.rearm();
+        }
+        self.status = Status::Following;
+
+        if let Some(contents) = contents {
+            self.push_lines(contents);
+        }
+        Ok(())
+    }
+
+    fn wait_for_file(&mut self, reason: &str, status: Status) {
+        // only report the transition, not every poll while the file is still missing
+        if self.status == Status::Following {
+            self.push_marker(reason);
+        }
+        self.status = status;
+        self.file = None;
+        self.pos = 0;
+        self.size = 0;
+    }
+
+    fn read_new_lines(&mut self) -> io::Result<()> {
+        if let Some(contents) = self.read_new_contents()? {
+            self.push_lines(contents);
+        }
+        Ok(())
+    }
+
+    /// Reads the complete lines appended since the last read, if there are any
+    fn read_new_contents(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some(f) = self.file.as_mut() else {
+            return Ok(None);
+        };
+
+        f.seek(SeekFrom::Start(self.pos))?;
+        let mut new_contents = vec![];
+        f.read_to_end(&mut new_contents)?;
+        self.size = self.pos + new_contents.len() as u64;
+
+        // only consume complete lines. a partial one is left to be read again once it's finished
+        let Some(end) = new_contents.iter().rposition(|&b| b == b'\n') else {
+            return Ok(None);
+        };
+        new_contents.truncate(end);
+        self.pos += end as u64 + 1;
+
+        Ok(Some(new_contents))
+    }
+
+    fn push_lines(&mut self, contents: Vec<u8>) {
+        // the parser stops at the first invalid byte, which would silently drop the rest
+        let contents = String::from_utf8_lossy(&contents);
+        // push each new line to history
+        for line in contents.as_bytes().into_text().unwrap().lines {
+            let parsed = self.parser.parse(&line::text(&line));
+            line::push(&mut self.history, line, parsed);
+        }
+    }
+
+    fn push_marker(&mut self, what: &str) {
+        line::push(
+            &mut self.history,
+            Spans::from(Span::styled(
+                format!("--- {} {} ---", self.path, what),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Parsed::default(),
+        );
     }
 }
 
@@ -41,38 +240,331 @@ impl Watcher for FileWatcher {
         // trigger a first read on startup
         tx.send(()).unwrap();
 
-        let path = self.path.clone();
+        let path = PathBuf::from(&self.path);
+        // the file, or any of the directories leading to it when they're still missing
+        let names: Vec<OsString> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.into()),
+                _ => None,
+            })
+            .collect();
+
         let mut watcher = recommended_watcher(move |res| match res {
-            // only notify for Modify events
-            Ok(Event {
-                kind: EventKind::Modify(..),
-                ..
-            }) => tx.send(()).unwrap(),
+            // the directory watch reports events for every entry, so only keep the ones about our
+            // file, or a directory on the way to it. creations and renames there are what tells us
+            // the file was rotated
+            Ok(Event { kind, paths, .. })
+                if !matches!(kind, EventKind::Access(..))
+                    && paths
+                        .iter()
+                        .any(|p| names.iter().any(|n| p.file_name() == Some(n))) =>
+            {
+                tx.send(()).unwrap()
+            }
             Ok(_) => {}
 
             Err(e) => println!("error: {:?}", e),
         })?;
-        watcher.watch(Path::new(&path), notify::RecursiveMode::NonRecursive)?;
-
+        // the file may not exist yet, in which case the directory watch lets us know once it's
+        // created, and the file watch is set up then
+        let _ = watcher.watch(&path, notify::RecursiveMode::NonRecursive);
         self.handle = Some(watcher);
+        self.watch_dir()
+            .with_context(|| format!("cannot watch the directory of {}", self.path))?;
 
         Ok(())
     }
 
     fn poll(&mut self) {
-        let mut f = std::fs::File::open(&self.path).unwrap();
-        let new_len = f.metadata().unwrap().len();
+        match self.follow() {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.wait_for_file("was removed", Status::Waiting);
+                // one more of the missing directories may have been created
+                let _ = self.watch_dir();
+            }
+            Err(e) => self.wait_for_file(
+                &format!("could not be read: {}", e),
+                Status::Failed(e.to_string()),
+            ),
+        }
+    }
 
-        // read new contents
-        f.seek(SeekFrom::Start(self.pos)).unwrap();
-        let mut new_contents = vec![];
-        f.read_to_end(&mut new_contents).unwrap();
-        // f.read_to_string(&mut new_contents).unwrap();
-        self.pos = new_len;
+    fn history(&self) -> &CircularBuffer<Line> {
+        &self.history
+    }
 
-        // push each new line to history
-        for line in new_contents.into_text().unwrap().lines.iter() {
-            self.history.push(line.clone());
+    fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+
+    fn pos(&self) -> Option<u64> {
+        Some(self.pos)
+    }
+
+    fn status(&self) -> Option<String> {
+        match &self.status {
+            Status::Waiting => Some("waiting for file".into()),
+            Status::Failed(e) => Some(format!("cannot read: {}", e)),
+            _ => None,
         }
     }
+
+    fn stop(&mut self) {
+        self.handle = None;
+        self.file = None;
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+// without inode numbers rotation can't be told apart from a regular write
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+/// Finds the offset at which the last `n` lines of the file start, by reading it backwards
+fn tail_offset(f: &mut File, n: usize) -> io::Result<u64> {
+    let len = f.seek(SeekFrom::End(0))?;
+    let mut reader = RevBufReader::new(f);
+    let mut offset = len;
+    let mut line = vec![];
+
+    for _ in 0..n {
+        line.clear();
+        match reader.read_until(b'\n', &mut line)? {
+            0 => break,
+            read => offset -= read as u64,
+        }
+    }
+
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tail_offset, FileWatcher, Options, Status};
+    use crate::{format::Parser, watchers::Watcher};
+    use std::io::Write;
+    use tokio::sync::mpsc;
+
+    fn lines(watcher: &FileWatcher) -> Vec<String> {
+        watcher
+            .history
+            .iter()
+            .map(|l| l.spans.0.iter().map(|s| s.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn tail_offset_finds_last_lines() {
+        let path = std::env::temp_dir().join(format!("ninetails-tail-{}", std::process::id()));
+        let mut f = std::fs::File::create(&path).unwrap();
+        f.write_all(b"one\ntwo\nthree\n").unwrap();
+
+        let mut f = std::fs::File::open(&path).unwrap();
+        assert_eq!(tail_offset(&mut f, 0).unwrap(), 14);
+        assert_eq!(tail_offset(&mut f, 1).unwrap(), 8);
+        assert_eq!(tail_offset(&mut f, 2).unwrap(), 4);
+        assert_eq!(tail_offset(&mut f, 10).unwrap(), 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn follows_truncation_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("ninetails-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let append = |contents: &[u8]| {
+            let mut f = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap();
+            f.write_all(contents).unwrap();
+        };
+
+        append(b"one\n");
+        let watcher = FileWatcher::new(
+            path.to_str().unwrap(),
+            100,
+            Options {
+                drain_rotated: true,
+                ..Default::default()
+            },
+            Parser::default(),
+        )
+        .unwrap();
+        let mut watcher = watcher.blocking_lock();
+        watcher.poll();
+
+        std::fs::write(&path, b"2\n").unwrap();
+        watcher.poll();
+        assert_eq!(
+            lines(&watcher)[1..],
+            [
+                format!("--- {} was truncated ---", watcher.path),
+                "2".into()
+            ]
+        );
+
+        // a partial line counts towards the size, but isn't read yet
+        append(b"thr");
+        watcher.poll();
+        assert_eq!((watcher.pos, watcher.size), (2, 5));
+
+        append(b"ee\n");
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        append(b"four\n");
+        watcher.poll();
+        assert_eq!(
+            lines(&watcher)[3..],
+            [
+                "three".into(),
+                format!("--- {} was rotated ---", watcher.path),
+                "four".into()
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn waits_for_missing_file() {
+        let dir = std::env::temp_dir().join(format!("ninetails-missing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+
+        let watcher = FileWatcher::new(
+            path.to_str().unwrap(),
+            100,
+            Options::default(),
+            Parser::default(),
+        )
+        .unwrap();
+        let mut watcher = watcher.blocking_lock();
+        watcher.poll();
+        assert_eq!(watcher.status, Status::Waiting);
+        assert!(lines(&watcher).is_empty());
+
+        std::fs::write(&path, b"one\n").unwrap();
+        watcher.poll();
+        assert_eq!(watcher.status, Status::Following);
+
+        std::fs::remove_file(&path).unwrap();
+        watcher.poll();
+        assert_eq!(watcher.status, Status::Waiting);
+        assert_eq!(
+            lines(&watcher),
+            [
+                format!("--- {} was created ---", watcher.path),
+                "one".into(),
+                format!("--- {} was removed ---", watcher.path),
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn waits_for_missing_directories() {
+        let root = std::env::temp_dir().join(format!("ninetails-nodir-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let dir = root.join("a").join("b");
+        let path = dir.join("app.log");
+
+        let watcher = FileWatcher::new(
+            path.to_str().unwrap(),
+            100,
+            Options::default(),
+            Parser::default(),
+        )
+        .unwrap();
+        let mut watcher = watcher.blocking_lock();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        watcher.start(tx).unwrap();
+        assert_eq!(watcher.dir.as_deref(), Some(root.as_path()));
+        watcher.poll();
+        assert_eq!(watcher.status, Status::Waiting);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        watcher.poll();
+        assert_eq!(watcher.dir.as_deref(), Some(dir.as_path()));
+
+        // the file showing up is noticed from the new watch
+        while rx.try_recv().is_ok() {}
+        std::fs::write(&path, b"one\n").unwrap();
+        let notified = (0..100).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            rx.try_recv().is_ok()
+        });
+        assert!(notified);
+        watcher.poll();
+        assert_eq!(watcher.status, Status::Following);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reads_past_invalid_utf8() {
+        let path = std::env::temp_dir().join(format!("ninetails-latin1-{}", std::process::id()));
+        std::fs::write(&path, b"caf\xe9\nnext\n").unwrap();
+
+        let watcher = FileWatcher::new(
+            path.to_str().unwrap(),
+            100,
+            Options::default(),
+            Parser::default(),
+        )
+        .unwrap();
+        let mut watcher = watcher.blocking_lock();
+        watcher.poll();
+        assert_eq!(lines(&watcher), ["caf\u{fffd}", "next"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_read_errors() {
+        let dir = std::env::temp_dir().join(format!("ninetails-unreadable-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let watcher = FileWatcher::new(
+            dir.to_str().unwrap(),
+            100,
+            Options::default(),
+            Parser::default(),
+        )
+        .unwrap();
+        let mut watcher = watcher.blocking_lock();
+        watcher.poll();
+        assert!(matches!(watcher.status, Status::Failed(_)));
+        assert!(watcher.status().unwrap().starts_with("cannot read: "));
+
+        // it opens fine every time, but that's not enough to count as readable again
+        watcher.poll();
+        assert!(matches!(watcher.status, Status::Failed(_)));
+        assert!(lines(&watcher).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
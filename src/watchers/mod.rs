@@ -5,14 +5,57 @@ use tokio::sync::{
     Mutex,
 };
 
+use crate::{circular::CircularBuffer, line::Line};
+
+pub mod command;
+pub mod discovery;
 pub mod file;
+pub mod stdin;
+
+/// lines kept per source unless configured otherwise
+pub const DEFAULT_HISTORY: usize = 10000;
+
+pub type SharedWatcher = Arc<Mutex<dyn Watcher + Send>>;
 
 pub trait Watcher {
     fn start(&mut self, tx: UnboundedSender<()>) -> anyhow::Result<()>;
     fn poll(&mut self);
+    fn history(&self) -> &CircularBuffer<Line>;
+
+    /// Forgets the lines read so far
+    fn clear(&mut self);
+
+    /// Name of the source, used as the pane title
+    fn name(&self) -> String;
+
+    /// Path of the watched file, if any. Pane titles for files are shortened to tell them apart
+    fn path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Size of the watched file and how far into it we've read, if it's a file
+    fn size(&self) -> Option<u64> {
+        None
+    }
+
+    fn pos(&self) -> Option<u64> {
+        None
+    }
+
+    /// Short description of the state of the source, shown next to its title
+    fn status(&self) -> Option<String> {
+        None
+    }
+
+    fn restart(&mut self) {}
+
+    fn kill(&mut self) {}
+
+    /// Stops following the source for good, so that the task listening to it can finish
+    fn stop(&mut self) {}
 }
 
-pub async fn listen<W: Watcher + Send + 'static>(
+pub async fn listen<W: Watcher + Send + ?Sized + 'static>(
     obj: &Arc<Mutex<W>>,
     outer_tx: Sender<()>,
 ) -> anyhow::Result<()> {
@@ -22,18 +65,13 @@ pub async fn listen<W: Watcher + Send + 'static>(
     // setup a task to listen to file changes and read new lines
     let copy = obj.clone();
     tokio::task::spawn(async move {
-        loop {
-            match inner_rx.recv().await {
-                // file was modified
-                Some(_) => {
-                    let mut watcher = copy.lock().await;
-                    watcher.poll();
-
-                    // ping the outer channel to trigger a re-render
-                    outer_tx.send(()).await.unwrap();
-                }
-                _ => {}
-            }
+        // file was modified
+        while inner_rx.recv().await.is_some() {
+            let mut watcher = copy.lock().await;
+            watcher.poll();
+
+            // ping the outer channel to trigger a re-render
+            outer_tx.send(()).await.unwrap();
         }
     });
 
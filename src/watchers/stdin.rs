@@ -0,0 +1,105 @@
+use ansi_to_tui::IntoText;
+use std::{
+    io::{self, BufRead},
+    sync::Arc,
+};
+use tokio::sync::{
+    mpsc::{self, error::TryRecvError, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+
+use crate::{
+    circular::CircularBuffer,
+    format::Parser,
+    line::{self, Line},
+};
+
+use super::Watcher;
+
+/// Follows whatever is piped into the process. Keyboard input is then read from the terminal
+/// directly by crossterm
+pub struct StdinWatcher {
+    pub history: CircularBuffer<Line>,
+    /// set once the writing end of the pipe is closed
+    pub closed: bool,
+    lines: Option<UnboundedReceiver<Vec<u8>>>,
+    parser: Parser,
+}
+
+impl StdinWatcher {
+    pub fn new(history: usize, parser: Parser) -> anyhow::Result<Arc<Mutex<Self>>> {
+        Ok(Arc::new(Mutex::new(Self {
+            history: CircularBuffer::new(history),
+            closed: false,
+            lines: None,
+            parser,
+        })))
+    }
+}
+
+impl Watcher for StdinWatcher {
+    fn start(&mut self, tx: UnboundedSender<()>) -> anyhow::Result<()> {
+        let (lines_tx, lines_rx) = mpsc::unbounded_channel();
+
+        // a blocking read on stdin can't be cancelled, so use a plain thread rather than tokio's
+        // blocking pool, which would keep the runtime from shutting down until the next line
+        std::thread::spawn(move || {
+            for line in io::stdin().lock().split(b'\n') {
+                let Ok(line) = line else {
+                    break;
+                };
+                if lines_tx.send(line).is_err() {
+                    break;
+                }
+                let _ = tx.send(());
+            }
+            // dropping the sender is how poll learns the input is over
+            drop(lines_tx);
+            let _ = tx.send(());
+        });
+
+        self.lines = Some(lines_rx);
+
+        Ok(())
+    }
+
+    fn poll(&mut self) {
+        let Some(lines) = self.lines.as_mut() else {
+            return;
+        };
+
+        loop {
+            match lines.try_recv() {
+                Ok(line) => {
+                    // the parser stops at the first invalid byte, which would cut the line short
+                    let line = String::from_utf8_lossy(&line);
+                    for line in line.as_bytes().into_text().unwrap().lines {
+                        let parsed = self.parser.parse(&line::text(&line));
+                        line::push(&mut self.history, line, parsed);
+                    }
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+    }
+
+    fn history(&self) -> &CircularBuffer<Line> {
+        &self.history
+    }
+
+    fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    fn name(&self) -> String {
+        "stdin".into()
+    }
+
+    fn status(&self) -> Option<String> {
+        self.closed.then(|| "end of input".into())
+    }
+}